@@ -0,0 +1,475 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+use rust_i18n::t;
+use tokio::sync::{broadcast, mpsc, Mutex};
+
+use crate::{
+    commands::{Args, Command, CommandHandler, Reply},
+    config::EventFilter,
+    error::Error,
+    events::{Category, Event},
+};
+
+/// What a chatter is allowed to do, from least to most
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    Everyone,
+    #[serde(alias = "sub")]
+    Subscriber,
+    Vip,
+    #[serde(alias = "mod")]
+    Moderator,
+    Admin,
+    Broadcaster,
+}
+
+/// A message somebody sent in chat
+#[derive(Debug, Clone)]
+pub struct ChatMessage {
+    pub channel: String,
+    pub user: String,
+    /// The highest role of the user
+    pub role: Role,
+    pub text: String,
+}
+
+/// Overrides of the bot settings for a single channel
+#[derive(Debug, Clone, Default)]
+pub struct ChannelSettings {
+    pub lang: Option<String>,
+    /// Names of the enabled commands, all commands are enabled when not set
+    pub commands: Option<Vec<String>>,
+    /// All notifications are sent when not set
+    pub events: Option<EventFilter>,
+}
+
+impl ChannelSettings {
+    pub fn allows_command(&self, command: Command) -> bool {
+        match &self.commands {
+            Some(commands) => commands.iter().any(|c| c.eq_ignore_ascii_case(command.name())),
+            None => true,
+        }
+    }
+
+    pub fn allows_event(&self, event: &Event) -> bool {
+        match &self.events {
+            Some(events) => events.allows(event.category()),
+            None => true,
+        }
+    }
+}
+
+/// A chat the bot can talk in, like Twitch or YouTube
+#[async_trait]
+pub trait ChatPlatform: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Only moderators and up are allowed to use any command
+    fn mod_only(&self) -> bool;
+
+    /// The channels the monitor notifications are sent to
+    async fn channels(&self) -> Vec<String>;
+
+    async fn send(&self, channel: &str, message: String);
+
+    fn settings(&self, _channel: &str) -> ChannelSettings {
+        ChannelSettings::default()
+    }
+
+    /// Receives chat messages, only returns when the chat can't be used anymore
+    async fn receive(&self, messages: mpsc::UnboundedSender<ChatMessage>) -> Result<(), Error>;
+}
+
+/// Where the monitor notifications end up
+#[async_trait]
+pub trait ChatSink: Send + Sync {
+    async fn say(&self, event: &Event, lang: &str);
+}
+
+/// Says the notifications in every channel of a chat platform
+pub struct PlatformSink(pub Arc<dyn ChatPlatform>);
+
+#[async_trait]
+impl ChatSink for PlatformSink {
+    async fn say(&self, event: &Event, lang: &str) {
+        for channel in self.0.channels().await {
+            let settings = self.0.settings(&channel);
+
+            if !settings.allows_event(event) {
+                continue;
+            }
+
+            let lang = settings.lang.as_deref().unwrap_or(lang);
+            self.0.send(&channel, event.message(lang)).await;
+        }
+    }
+}
+
+/// Prints the notifications, used when no chat is configured
+pub struct LogSink;
+
+#[async_trait]
+impl ChatSink for LogSink {
+    async fn say(&self, event: &Event, lang: &str) {
+        println!("Liveu: {}", event.message(lang));
+    }
+}
+
+/// Sends every monitor event to the sink
+pub async fn announce(
+    sink: Arc<dyn ChatSink>,
+    lang: String,
+    mut events: broadcast::Receiver<Event>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        // chat already saw the command
+        if event.category() == Category::Command {
+            continue;
+        }
+
+        sink.say(&event, &lang).await;
+    }
+}
+
+/// Replies in the channel the command came from
+struct ChatReply {
+    platform: Arc<dyn ChatPlatform>,
+    channel: String,
+}
+
+#[async_trait]
+impl Reply for ChatReply {
+    async fn reply(&self, message: String) {
+        self.platform.send(&self.channel, message).await;
+    }
+}
+
+/// When commands can be used again
+#[derive(Default)]
+struct Cooldowns {
+    commands: HashMap<Command, Instant>,
+    users: HashMap<String, Instant>,
+    /// Users that were told the command is on cooldown, until the cooldown ends
+    replied: HashMap<(Command, String), Instant>,
+}
+
+impl Cooldowns {
+    /// Time left before the user is allowed to run the command
+    fn remaining(&self, command: Command, user: &str, now: Instant) -> Option<Duration> {
+        let command = self.commands.get(&command);
+        let user = self.users.get(user);
+
+        command
+            .into_iter()
+            .chain(user)
+            .max()
+            .filter(|until| **until > now)
+            .map(|until| *until - now)
+    }
+
+    /// Returns true only the first time the user hits the cooldown of the command,
+    /// so spamming a command doesn't make the bot spam the chat
+    fn reply_once(&mut self, command: Command, user: &str, remaining: Duration, now: Instant) -> bool {
+        self.replied.retain(|_, until| *until > now);
+
+        let key = (command, user.to_owned());
+
+        if self.replied.contains_key(&key) {
+            return false;
+        }
+
+        self.replied.insert(key, now + remaining);
+        true
+    }
+
+    fn start(&mut self, command: Command, command_secs: u16, user: &str, user_secs: u16, now: Instant) {
+        // don't keep every user that ever used a command
+        self.users.retain(|_, until| *until > now);
+
+        self.commands
+            .insert(command, now + Duration::from_secs(command_secs as u64));
+
+        if user_secs > 0 {
+            self.users
+                .insert(user.to_owned(), now + Duration::from_secs(user_secs as u64));
+        }
+    }
+}
+
+/// A command waiting to be confirmed
+struct Confirmation {
+    command: Command,
+    args: Args,
+    until: Instant,
+}
+
+/// Runs the commands of a chat platform and announces the monitor events in it
+pub struct Chat {
+    platform: Arc<dyn ChatPlatform>,
+    handler: CommandHandler,
+    cooldowns: Mutex<Cooldowns>,
+    /// Commands waiting to be confirmed by (channel, user)
+    confirmations: Mutex<HashMap<(String, String), Confirmation>>,
+}
+
+impl Chat {
+    /// The returned handle finishes when the platform stops receiving messages
+    pub fn run(
+        platform: Arc<dyn ChatPlatform>,
+        handler: CommandHandler,
+    ) -> tokio::task::JoinHandle<Result<(), Error>> {
+        tokio::spawn(announce(
+            Arc::new(PlatformSink(platform.clone())),
+            handler.lang.to_owned(),
+            handler.events.subscribe(),
+        ));
+
+        let (sender, mut messages) = mpsc::unbounded_channel();
+        let receiver = platform.clone();
+
+        tokio::spawn(async move {
+            let chat = Self {
+                platform,
+                handler,
+                cooldowns: Mutex::new(Cooldowns::default()),
+                confirmations: Mutex::new(HashMap::new()),
+            };
+
+            while let Some(message) = messages.recv().await {
+                chat.handle_message(message).await;
+            }
+        });
+
+        tokio::spawn(async move { receiver.receive(sender).await })
+    }
+
+    async fn handle_message(&self, msg: ChatMessage) {
+        if self.platform.mod_only() && msg.role < Role::Moderator {
+            return;
+        }
+
+        let trigger = msg.text.split_ascii_whitespace().next().unwrap_or("");
+        let mut words: Vec<&str> = msg.text.split_ascii_whitespace().skip(1).collect();
+        let confirmed = words.last().map(|w| w.eq_ignore_ascii_case("confirm")) == Some(true);
+
+        if confirmed {
+            words.pop();
+        }
+
+        // the command as it has to be confirmed, with its arguments but without `confirm`
+        let command_text = std::iter::once(trigger)
+            .chain(words.iter().copied())
+            .collect::<Vec<&str>>()
+            .join(" ");
+        let args = Args::parse(words.into_iter());
+
+        let command = self.handler.get_command(trigger);
+        let settings = self.platform.settings(&msg.channel);
+
+        if command == Command::Unknown || !settings.allows_command(command) {
+            return;
+        }
+
+        if msg.role < self.handler.required_role(command) {
+            return;
+        }
+
+        let mut handler = self.handler.clone();
+        if let Some(lang) = &settings.lang {
+            handler.lang = lang.to_owned();
+        }
+
+        if !self.check_confirmation(&handler, command, &args, &command_text, confirmed, &msg).await {
+            return;
+        }
+
+        if !self.check_cooldown(&handler, command, &msg).await {
+            return;
+        }
+
+        let res = if command == Command::Help {
+            Ok(handler.help_message(msg.role, |c| settings.allows_command(c)))
+        } else if !command.controls_unit() {
+            handler.handle_non_permission_commands(command, &args).await
+        } else {
+            handler.audit(
+                msg.text.trim(),
+                &msg.user,
+                &format!("{} {}", self.platform.name(), msg.channel),
+            );
+
+            let reply = Arc::new(ChatReply {
+                platform: self.platform.clone(),
+                channel: msg.channel.to_owned(),
+            });
+
+            handler.handle_permission_commands(command, &args, reply)
+                .await
+        };
+
+        if let Ok(res) = res {
+            self.platform.send(&msg.channel, res).await;
+        }
+    }
+
+    /// Asks to confirm the command when needed, returns true when it can run.
+    /// Only the user who asked can confirm, and only in the same channel.
+    async fn check_confirmation(
+        &self,
+        handler: &CommandHandler,
+        command: Command,
+        args: &Args,
+        command_text: &str,
+        confirmed: bool,
+        msg: &ChatMessage,
+    ) -> bool {
+        let needs_confirmation = handler
+            .command_settings(command)
+            .map(|c| c.confirm)
+            .unwrap_or(false);
+
+        if !needs_confirmation {
+            return true;
+        }
+
+        let now = Instant::now();
+        let key = (msg.channel.to_owned(), msg.user.to_owned());
+        let mut confirmations = self.confirmations.lock().await;
+        confirmations.retain(|_, c| c.until > now);
+
+        if confirmed {
+            let pending = confirmations.get(&key);
+
+            if pending.map(|c| (c.command, &c.args)) == Some((command, args)) {
+                confirmations.remove(&key);
+                return true;
+            }
+
+            // a confirm never asks again, that could confirm something else than what was asked
+            drop(confirmations);
+
+            let message = t!(
+                "twitch.nothing_to_confirm",
+                locale = &handler.lang,
+                user = &msg.user,
+                command = command_text
+            );
+            self.platform.send(&msg.channel, message).await;
+
+            return false;
+        }
+
+        let timeout = handler.config.commands.confirm_timeout;
+        confirmations.insert(
+            key,
+            Confirmation {
+                command,
+                args: args.to_owned(),
+                until: now + Duration::from_secs(timeout as u64),
+            },
+        );
+        drop(confirmations);
+
+        let message = t!(
+            "twitch.confirm",
+            locale = &handler.lang,
+            user = &msg.user,
+            command = command_text,
+            seconds = &timeout.to_string()
+        );
+        self.platform.send(&msg.channel, message).await;
+
+        false
+    }
+
+    /// Starts the cooldowns, returns false when the command is still on cooldown.
+    /// Admins and the broadcaster can always use commands.
+    async fn check_cooldown(&self, handler: &CommandHandler, command: Command, msg: &ChatMessage) -> bool {
+        let now = Instant::now();
+        let mut cooldowns = self.cooldowns.lock().await;
+
+        if msg.role < Role::Admin {
+            if let Some(remaining) = cooldowns.remaining(command, &msg.user, now) {
+                let reply = handler.config.commands.cooldown_reply
+                    && cooldowns.reply_once(command, &msg.user, remaining, now);
+                drop(cooldowns);
+
+                if reply {
+                    let seconds = remaining.as_secs_f32().ceil().to_string();
+                    let message = t!(
+                        "twitch.on_cooldown",
+                        locale = &handler.lang,
+                        command = msg.text.split_ascii_whitespace().next().unwrap_or(""),
+                        seconds = &seconds
+                    );
+                    self.platform.send(&msg.channel, message).await;
+                }
+
+                return false;
+            }
+        }
+
+        cooldowns.start(
+            command,
+            handler.cooldown(command),
+            &msg.user,
+            handler.config.commands.user_cooldown,
+            now,
+        );
+
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn secs(secs: u64) -> Duration {
+        Duration::from_secs(secs)
+    }
+
+    #[test]
+    fn remaining_uses_the_longest_cooldown() {
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        assert_eq!(cooldowns.remaining(Command::Stats, "alice", now), None);
+
+        cooldowns.start(Command::Stats, 10, "alice", 30, now);
+
+        assert_eq!(cooldowns.remaining(Command::Stats, "alice", now + secs(5)), Some(secs(25)));
+        // the command cooldown applies to everyone, the user cooldown to every command
+        assert_eq!(cooldowns.remaining(Command::Stats, "bob", now + secs(5)), Some(secs(5)));
+        assert_eq!(cooldowns.remaining(Command::Battery, "alice", now + secs(5)), Some(secs(25)));
+        assert_eq!(cooldowns.remaining(Command::Battery, "bob", now + secs(5)), None);
+        assert_eq!(cooldowns.remaining(Command::Stats, "alice", now + secs(30)), None);
+    }
+
+    #[test]
+    fn reply_once_per_cooldown() {
+        let mut cooldowns = Cooldowns::default();
+        let now = Instant::now();
+
+        assert!(cooldowns.reply_once(Command::Stats, "alice", secs(10), now));
+        assert!(!cooldowns.reply_once(Command::Stats, "alice", secs(9), now + secs(1)));
+        assert!(cooldowns.reply_once(Command::Stats, "bob", secs(9), now + secs(1)));
+        assert!(cooldowns.reply_once(Command::Battery, "alice", secs(9), now + secs(1)));
+
+        // told again once the cooldown is over
+        assert!(cooldowns.reply_once(Command::Stats, "alice", secs(10), now + secs(10)));
+    }
+}
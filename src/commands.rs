@@ -0,0 +1,996 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use rust_i18n::t;
+use tokio::sync::{broadcast, Mutex};
+
+use crate::{
+    chat::Role,
+    config,
+    error::Error,
+    events::Event,
+    liveu::{self, Liveu},
+    liveu_monitor::Modem,
+    nginx, srt,
+};
+
+/// Where the messages of a running command go
+#[async_trait]
+pub trait Reply: Send + Sync {
+    async fn reply(&self, message: String);
+}
+
+/// Runs the chat commands, independent of where the command came from
+#[derive(Clone)]
+pub struct CommandHandler {
+    pub liveu: Liveu,
+    pub boss_id: String,
+    /// The unit the monitors watch, `boss_id` changes with `unit=<name>`
+    pub monitored_boss_id: String,
+    pub config: config::Config,
+    pub lang: String,
+    pub modem_sync: Arc<Mutex<Vec<Modem>>>,
+    pub battery_sync: Arc<Mutex<liveu::Battery>>,
+    /// The status and latest stats of the srt publisher
+    pub srt_sync: Arc<Mutex<srt::SrtStatus>>,
+    pub events: broadcast::Sender<Event>,
+}
+
+impl CommandHandler {
+    /// Tells everyone listening who is about to control the unit
+    pub fn audit(&self, command: &str, user: &str, channel: &str) {
+        let _ = self.events.send(Event::CommandExecuted {
+            command: command.to_owned(),
+            user: user.to_owned(),
+            channel: channel.to_owned(),
+        });
+    }
+
+    pub async fn handle_non_permission_commands(
+        &self,
+        command: Command,
+        args: &Args,
+    ) -> Result<String, Error> {
+        // everyone that can use the other frontends is allowed to use every command
+        if command == Command::Help {
+            return Ok(self.help_message(Role::Broadcaster, |_| true));
+        }
+
+        let (handler, cached) = match self.prepare(command, args).await? {
+            Ok(prepared) => prepared,
+            Err(usage) => return Ok(usage),
+        };
+
+        match command {
+            Command::Stats => handler.generate_liveu_modems_message(cached, args.verbose()).await,
+            Command::Battery => handler.generate_liveu_battery_message(cached).await,
+            Command::Custom(index) => handler.generate_custom_message(index, cached).await,
+            // the srt server belongs to the main unit
+            Command::Srt => self.generate_srt_message().await,
+            _ => unreachable!(),
+        }
+    }
+
+    pub async fn handle_permission_commands(
+        &self,
+        command: Command,
+        args: &Args,
+        reply: Arc<dyn Reply>,
+    ) -> Result<String, Error> {
+        let (handler, _) = match self.prepare(command, args).await? {
+            Ok(prepared) => prepared,
+            Err(usage) => return Ok(usage),
+        };
+
+        match command {
+            Command::Start => handler.generate_liveu_start_message(reply).await,
+            Command::Stop => handler.generate_liveu_stop_message(reply).await,
+            Command::Restart => handler.generate_liveu_restart_message(reply).await,
+            Command::Reboot => handler.generate_liveu_reboot_message(reply).await,
+            Command::Delay => handler.toggle_delay(reply, args.delay()).await,
+            Command::Modem => handler.set_modem(&args.values[0], args.modem_enabled() == Some(true)).await,
+            _ => unreachable!(),
+        }
+    }
+
+    /// Checks the arguments and picks the unit to use.
+    /// Returns the handler and if the monitored stats can be used, or the message for the user.
+    async fn prepare(
+        &self,
+        command: Command,
+        args: &Args,
+    ) -> Result<Result<(CommandHandler, bool), String>, Error> {
+        if !args.is_valid(command) {
+            return Ok(Err(t!(
+                "twitch.usage",
+                locale = &self.lang,
+                usage = &self.usage(command)
+            )));
+        }
+
+        let unit = match &args.unit {
+            Some(unit) => unit.to_lowercase(),
+            None => return Ok(Ok((self.clone(), true))),
+        };
+
+        let inventories = self.liveu.get_inventories().await?;
+        let found = inventories
+            .units
+            .into_iter()
+            .find(|u| u.name.to_lowercase() == unit || u.id.to_lowercase() == unit);
+
+        match found {
+            Some(found) if found.id == self.boss_id => Ok(Ok((self.clone(), true))),
+            Some(found) => {
+                let mut handler = self.clone();
+                handler.boss_id = found.id;
+                Ok(Ok((handler, false)))
+            }
+            None => Ok(Err(t!("twitch.unknown_unit", locale = &self.lang, unit = &unit))),
+        }
+    }
+
+    /// The stream monitor only watches the main unit
+    fn is_stream_monitored(&self) -> bool {
+        self.config.liveu.monitor.stream && self.boss_id == self.monitored_boss_id
+    }
+
+    /// Lists the triggers of the commands the role is allowed to use
+    pub fn help_message(&self, role: Role, allowed: impl Fn(Command) -> bool) -> String {
+        let commands: Vec<String> = self
+            .all_commands()
+            .into_iter()
+            .filter(|&command| command != Command::Help && allowed(command))
+            .filter(|&command| role >= self.required_role(command))
+            .filter_map(|command| self.command_settings(command))
+            .filter(|c| c.enabled && !c.triggers.is_empty())
+            .map(|c| c.triggers.join("/"))
+            .collect();
+
+        t!("twitch.help", locale = &self.lang, commands = &commands.join(", "))
+    }
+
+    /// How to use the command, e.g. `!ludelay [500-60000] [unit=<name>]`
+    pub fn usage(&self, command: Command) -> String {
+        let trigger = self
+            .command_settings(command)
+            .and_then(|c| c.triggers.first())
+            .map(|t| t.as_str())
+            .unwrap_or("");
+
+        let args = match command {
+            Command::Stats => " [verbose]".to_string(),
+            Command::Delay => format!(" [{}-{}]", MIN_DELAY, MAX_DELAY),
+            Command::Modem => " <port> on|off".to_string(),
+            _ => "".to_string(),
+        };
+
+        format!("{}{} [unit=<name>]", trigger, args)
+    }
+
+    /// The built in commands followed by the custom commands
+    pub fn all_commands(&self) -> Vec<Command> {
+        let custom = (0..self.config.commands.custom.len()).map(Command::Custom);
+        Command::ALL.into_iter().chain(custom).collect()
+    }
+
+    /// Finds the enabled command with this trigger
+    pub fn get_command(&self, trigger: &str) -> Command {
+        self.all_commands()
+            .into_iter()
+            .find(|&command| {
+                self.command_settings(command)
+                    .map(|c| c.enabled && c.triggers.iter().any(|t| t == trigger))
+                    .unwrap_or(false)
+            })
+            .unwrap_or(Command::Unknown)
+    }
+
+    /// Finds the enabled command with this name in the config, e.g. `stats`
+    pub fn get_command_by_name(&self, name: &str) -> Command {
+        let command = Command::from_name(name);
+
+        match self.command_settings(command) {
+            Some(settings) if settings.enabled => command,
+            _ => Command::Unknown,
+        }
+    }
+
+    pub fn command_settings(&self, command: Command) -> Option<&config::CommandSettings> {
+        let commands = &self.config.commands;
+
+        match command {
+            Command::Stats => Some(&commands.stats),
+            Command::Battery => Some(&commands.battery),
+            Command::Start => Some(&commands.start),
+            Command::Stop => Some(&commands.stop),
+            Command::Restart => Some(&commands.restart),
+            Command::Reboot => Some(&commands.reboot),
+            Command::Delay => Some(&commands.delay),
+            Command::Modem => Some(&commands.modem),
+            Command::Srt => Some(&commands.srt),
+            Command::Help => Some(&commands.help),
+            Command::Custom(index) => commands.custom.get(index).map(|c| &c.command),
+            Command::Unknown => None,
+        }
+    }
+
+    /// Lowest role allowed to use the command in chat
+    pub fn required_role(&self, command: Command) -> Role {
+        let default = match command {
+            Command::Stats | Command::Battery | Command::Srt | Command::Help | Command::Custom(_) => {
+                Role::Everyone
+            }
+            _ => Role::Admin,
+        };
+
+        self.command_settings(command)
+            .and_then(|c| c.role)
+            .unwrap_or(default)
+    }
+
+    /// Seconds before the command can be used again
+    pub fn cooldown(&self, command: Command) -> u16 {
+        self.command_settings(command)
+            .and_then(|c| c.cooldown)
+            .unwrap_or(self.config.commands.cooldown)
+    }
+
+    /// The connected modems and their signal quality, the signal is only known when asking the unit
+    async fn get_modems(&self, cached: bool, live: bool) -> Result<(Vec<Modem>, HashMap<String, u32>), Error> {
+        let mut interfaces: Vec<Modem> = Vec::new();
+        let mut signal = HashMap::new();
+        let polls = cached && (self.config.liveu.monitor.modems || self.config.polls_stats());
+
+        if polls && !live {
+            interfaces = (self.modem_sync.lock().await).clone();
+        } else {
+            for interface in self
+                .liveu
+                .get_unit_custom_names(&self.boss_id, self.config.custom_port_names.clone())
+                .await?
+            {  
+                signal.insert(interface.port.to_owned(), interface.signal_quality);
+                interfaces.push(Modem{
+                    port: interface.port.to_string(), 
+                    connected: interface.connected, 
+                    uplink_kbps: interface.uplink_kbps, 
+                    enabled: interface.enabled,
+                    technology: interface.technology,
+                    is_currently_roaming: interface.is_currently_roaming,
+                });
+            }
+        }
+
+        Ok((interfaces, signal))
+    }
+
+    /// The bitrates of the srt and rtmp server, these belong to the main unit
+    async fn get_server_bitrates(&self) -> (Option<i64>, Option<u32>) {
+        let srt = match self.config.srt {
+            Some(_) => Some(self.srt_sync.lock().await.bitrate()),
+            None => None,
+        };
+
+        let rtmp = match &self.config.rtmp {
+            Some(rtmp) => nginx::get_rtmp_bitrate(rtmp).await.ok().flatten(),
+            None => None,
+        };
+
+        (srt, rtmp)
+    }
+
+    async fn generate_liveu_modems_message(&self, cached: bool, verbose: bool) -> Result<String, Error> {
+        let (interfaces, signal) = self.get_modems(cached, verbose).await?;
+
+        if interfaces.is_empty() {
+            return Ok(t!("twitch.offline", locale = &self.lang));
+        }
+
+        let mut message = String::new();
+        let mut total_bitrate = 0;
+
+        for interface in interfaces.iter() {
+            let mut details: Vec<String> = Vec::new();
+
+            if !interface.technology.is_empty() {
+                details.push(interface.technology.to_owned());
+            }
+
+            if let Some(signal) = signal.get(&interface.port).filter(|_| verbose) {
+                details.push(format!("{}%", signal));
+            }
+
+            message = message
+                + &format!(
+                    "{}: {} Kbps{}{}, ",
+                    interface.port,
+                    interface.uplink_kbps,
+                    if !details.is_empty() {
+                        format!(" ({})", details.join(", "))
+                    } else {
+                        "".to_string()
+                    },
+                    if interface.is_currently_roaming {
+                        t!("twitch.roaming", locale = &self.lang)
+                    } else {
+                        "".to_string()
+                    }
+                );
+            total_bitrate += interface.uplink_kbps;
+        }
+
+        if total_bitrate == 0 {
+            return Ok(t!("twitch.online_ready", locale = &self.lang));
+        }
+
+        message += &format!("Total LRT: {} Kbps", total_bitrate);
+
+        if verbose {
+            if let Ok(video) = self.liveu.get_video(&self.boss_id).await {
+                if let Some(resolution) = video.resolution {
+                    message += &format!(", Video: {}", resolution);
+                }
+                if let Some(bitrate) = video.bitrate {
+                    message += &format!(", Video bitrate: {} Kbps", bitrate);
+                }
+            }
+
+            if let Ok(delay) = self.liveu.get_delay(&self.boss_id).await {
+                message += &format!(", Delay: {} ms", delay.delay);
+            }
+        }
+
+        // the srt and rtmp servers belong to the main unit
+        if !cached {
+            return Ok(message);
+        }
+
+        let (srt_bitrate, rtmp_bitrate) = self.get_server_bitrates().await;
+
+        if let Some(srt_bitrate) = srt_bitrate {
+            let status = self.srt_sync.lock().await.clone();
+
+            match status {
+                srt::SrtStatus::Live(_) => message += &format!(", SRT: {} Kbps", srt_bitrate),
+                status => message += &format!(", {}", self.srt_status_message(&status)),
+            }
+        }
+        if let Some(bitrate) = rtmp_bitrate {
+            message += &format!(", RTMP: {} Kbps", bitrate);
+        }
+
+        Ok(message)
+    }
+
+    async fn generate_srt_message(&self) -> Result<String, Error> {
+        if self.config.srt.is_none() {
+            return Ok(t!("twitch.srt_offline", locale = &self.lang));
+        }
+
+        let stat = match self.srt_sync.lock().await.clone() {
+            srt::SrtStatus::Live(stat) => stat,
+            status => return Ok(self.srt_status_message(&status)),
+        };
+
+        let hours = stat.uptime / 3600;
+        let minutes = stat.uptime % 3600 / 60;
+        let mut uptime = String::new();
+
+        if hours != 0 {
+            uptime += &t!("twitch.hours", locale = &self.lang, hour = &hours.to_string());
+        }
+
+        uptime += &t!("twitch.minutes", locale = &self.lang, minute = &minutes.to_string());
+
+        Ok(format!(
+            "SRT: {} Kbps, RTT: {:.1} ms, Loss: {} pkts, Drop: {} pkts, Bandwidth: {:.1} Mbps, Buffer: {} ms, Uptime:{}",
+            stat.bitrate,
+            stat.rtt,
+            stat.pkt_rcv_loss,
+            stat.pkt_rcv_drop,
+            stat.mbps_bandwidth,
+            stat.ms_rcv_buf,
+            uptime
+        ))
+    }
+
+    /// Why there are no srt stats
+    fn srt_status_message(&self, status: &srt::SrtStatus) -> String {
+        match status {
+            srt::SrtStatus::Pending => t!("twitch.srt_pending", locale = &self.lang),
+            srt::SrtStatus::Unreachable { .. } => t!("twitch.srt_unreachable", locale = &self.lang),
+            srt::SrtStatus::InvalidStats { .. } => t!("twitch.srt_invalid_stats", locale = &self.lang),
+            _ => t!("twitch.srt_offline", locale = &self.lang),
+        }
+    }
+
+    /// The battery of the unit, nothing when the unit is offline
+    async fn get_battery(&self, cached: bool) -> Option<liveu::Battery> {
+        let battery = if cached && (self.config.liveu.monitor.battery || self.config.polls_stats()) {
+            (self.battery_sync.lock().await).clone()
+        }else{
+            self.liveu.get_battery(&self.boss_id).await.ok()?
+        };
+
+        // 255 means we don't know
+        if battery.percentage == 255 {
+            return None;
+        }
+
+        Some(battery)
+    }
+
+    /// e.g. `1h 20m`, empty when the battery isn't being used
+    fn battery_time_left(&self, battery: &liveu::Battery) -> String {
+        if battery.run_time_to_empty == 0 || !battery.discharging {
+            return "".to_string();
+        }
+
+        let hours = battery.run_time_to_empty / 60;
+        let minutes = battery.run_time_to_empty % 60;
+        let mut time_string = String::new();
+
+        if hours != 0 {
+            time_string += &t!("twitch.hours", locale = &self.lang, hour = &hours.to_string());
+        }
+
+        time_string += &t!("twitch.minutes", locale = &self.lang, minute = &minutes.to_string());
+        time_string
+    }
+
+    fn battery_charging_status(&self, battery: &liveu::Battery) -> String {
+        if battery.charging {
+            t!("twitch.charging", locale = &self.lang)
+        } else if battery.percentage == 100 {
+            let mut s = t!("twitch.fully_charged", locale = &self.lang);
+
+            if battery.connected {
+                s += t!("twitch.connected", locale = &self.lang).as_str()
+            }
+
+            s
+        } else if battery.percentage < 100 && !battery.charging && !battery.discharging {
+            t!("twitch.too_hot", locale = &self.lang)
+        } else {
+            t!("twitch.not_charging", locale = &self.lang)
+        }
+    }
+
+    async fn generate_liveu_battery_message(&self, cached: bool) -> Result<String, Error> {
+        let battery = match self.get_battery(cached).await {
+            Some(battery) => battery,
+            None => return Ok(t!("twitch.offline", locale = &self.lang)),
+        };
+
+        let time_left = self.battery_time_left(&battery);
+        let estimated_battery_time = if time_left.is_empty() {
+            "".to_string()
+        } else {
+            t!("twitch.est_battery_time", locale = &self.lang, timeLeft = &time_left)
+        };
+
+        let charging = self.battery_charging_status(&battery);
+
+        let message = t!(
+            "twitch.battery_message",
+            locale = &self.lang,
+            percent = &battery.percentage.to_string(),
+            chargingORnot = &charging,
+            est_time = &estimated_battery_time
+        );
+
+        Ok(message)
+    }
+
+    /// Fills in the placeholders of the response of a custom command
+    async fn generate_custom_message(&self, index: usize, cached: bool) -> Result<String, Error> {
+        let custom = match self.config.commands.custom.get(index) {
+            Some(custom) => custom,
+            None => return Ok("".to_string()),
+        };
+
+        let (modems, _) = self.get_modems(cached, false).await?;
+        let battery = self.get_battery(cached).await;
+
+        if modems.is_empty() && battery.is_none() {
+            return Ok(t!("twitch.offline", locale = &self.lang));
+        }
+
+        let total_bitrate: u32 = modems.iter().map(|m| m.uplink_kbps).sum();
+
+        let modems_text = modems
+            .iter()
+            .map(|modem| {
+                let technology = custom
+                    .technologies
+                    .get(&modem.technology)
+                    .unwrap_or(&modem.technology);
+
+                let mut text = format!("{}: {} Kbps", modem.port, modem.uplink_kbps);
+
+                if !technology.is_empty() {
+                    text += &format!(" ({})", technology);
+                }
+
+                if modem.is_currently_roaming {
+                    text += &t!("twitch.roaming", locale = &self.lang);
+                }
+
+                text
+            })
+            .collect::<Vec<String>>()
+            .join(", ");
+
+        let (srt_bitrate, rtmp_bitrate) = if cached {
+            self.get_server_bitrates().await
+        } else {
+            (None, None)
+        };
+
+        let mut values = vec![
+            ("modems", modems_text),
+            ("modem_count", modems.len().to_string()),
+            ("total_lrt", total_bitrate.to_string()),
+            ("srt_bitrate", srt_bitrate.unwrap_or_default().to_string()),
+            ("rtmp_bitrate", rtmp_bitrate.unwrap_or_default().to_string()),
+        ];
+
+        match &battery {
+            Some(battery) => {
+                values.push(("battery.percentage", battery.percentage.to_string()));
+                values.push(("battery.eta", self.battery_time_left(battery)));
+                values.push(("battery.charging", self.battery_charging_status(battery)));
+            }
+            None => {
+                let unknown = t!("twitch.unknown", locale = &self.lang);
+                values.push(("battery.percentage", unknown.to_owned()));
+                values.push(("battery.eta", "".to_string()));
+                values.push(("battery.charging", unknown));
+            }
+        }
+
+        let mut message = custom.response.to_owned();
+
+        for (name, value) in values {
+            message = message.replace(&format!("{{{}}}", name), &value);
+        }
+
+        Ok(message)
+    }
+
+    async fn generate_liveu_start_message(&self, reply: Arc<dyn Reply>) -> Result<String, Error> {
+        let video = self.liveu.get_video(&self.boss_id).await;
+
+        let video = match video {
+            Ok(video) => video,
+            Err(_) => return Ok(t!("twitch.offline", locale = &self.lang)),
+        };
+
+        if video.resolution.is_none() {
+            return Ok(t!("twitch.no_camera", locale = &self.lang));
+        }
+
+        if video.bitrate.is_some() {
+            return Ok(t!("twitch.already_streaming", locale = &self.lang));
+        }
+
+        if self.liveu.start_stream(&self.boss_id).await.is_err() {
+            return Ok(t!("twitch.request_error", locale = &self.lang));
+        };
+
+        let confirm = DataUsedInThread {
+            reply,
+            liveu: self.liveu.clone(),
+            boss_id: self.boss_id.to_owned(),
+            lang: self.lang.to_owned(),
+            // the stream monitor already announces when the stream started or stopped
+            announce_success: !self.is_stream_monitored(),
+        };
+
+        tokio::spawn(async move {
+            confirm
+                .confirm_action(15, true, t!("twitch.started", locale = &confirm.lang), t!("twitch.starting", locale = &confirm.lang))
+                .await
+        });
+
+        Ok(t!("twitch.starting_stream", locale = &self.lang))
+    }
+
+    async fn generate_liveu_stop_message(&self, reply: Arc<dyn Reply>) -> Result<String, Error> {
+        if !self.liveu.is_streaming(&self.boss_id).await {
+            return Ok(t!("twitch.already_stopped", locale = &self.lang));
+        }
+
+        if self.liveu.stop_stream(&self.boss_id).await.is_err() {
+            return Ok(t!("twitch.request_error", locale = &self.lang));
+        };
+
+        let confirm = DataUsedInThread {
+            reply,
+            liveu: self.liveu.clone(),
+            boss_id: self.boss_id.to_owned(),
+            lang: self.lang.to_owned(),
+            // the stream monitor already announces when the stream started or stopped
+            announce_success: !self.is_stream_monitored(),
+        };
+
+        tokio::spawn(async move {
+            confirm
+                .confirm_action(10, false, t!("twitch.stopped", locale = &confirm.lang), t!("twitch.stopping", locale = &confirm.lang))
+                .await
+        });
+
+        Ok(t!("twitch.stopping_stream", locale = &self.lang))
+    }
+
+    async fn generate_liveu_restart_message(&self, reply: Arc<dyn Reply>) -> Result<String, Error> {
+        if !self.liveu.is_streaming(&self.boss_id).await {
+            return Ok(t!("twitch.not_streaming", locale = &self.lang));
+        }
+
+        let msg = t!("twitch.stream_restarting", locale = &self.lang);
+        reply.reply(msg).await;
+
+        self.generate_liveu_stop_message(reply.clone()).await?;
+        tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        self.generate_liveu_start_message(reply.clone())
+            .await?;
+
+        Ok(String::new())
+    }
+
+    async fn generate_liveu_reboot_message(&self, reply: Arc<dyn Reply>) -> Result<String, Error> {
+        let is_streaming = self.liveu.is_streaming(&self.boss_id).await;
+
+        let msg = t!("twitch.rebooting_message", locale = &self.lang);
+        reply.reply(msg).await;
+
+        if is_streaming {
+            self.generate_liveu_stop_message(reply.clone()).await?;
+            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        }
+
+        self.liveu.reboot_unit(&self.boss_id).await?;
+        tokio::time::sleep(tokio::time::Duration::from_secs(30)).await;
+
+        let mut attempts = 0;
+        let max_attempts = 20;
+
+        while !self.liveu.is_idle(&self.boss_id).await && attempts != max_attempts {
+            tokio::time::sleep(tokio::time::Duration::from_secs(10)).await;
+            attempts += 1;
+        }
+
+        if attempts == max_attempts {
+            return Ok(t!("twitch.reboot_too_long", locale = &self.lang));
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+
+        if is_streaming {
+            self.generate_liveu_start_message(reply.clone())
+                .await?;
+            return Ok(String::new());
+        }
+
+        Ok(t!("twitch.reboot_success", locale = &self.lang))
+    }
+
+    /// Sets the delay, or toggles between low delay and high resiliency without one
+    async fn toggle_delay(&self, reply: Arc<dyn Reply>, delay: Option<u64>) -> Result<String, Error> {
+        let is_streaming = self.liveu.is_streaming(&self.boss_id).await;
+
+        if is_streaming {
+            self.generate_liveu_stop_message(reply.clone()).await?;
+            tokio::time::sleep(tokio::time::Duration::from_secs(4)).await;
+        }
+
+        let delay = match delay {
+            Some(delay) => (
+                delay,
+                t!("twitch.delay_set", locale = &self.lang, delay = &delay.to_string()),
+            ),
+            None => {
+                let current_delay = self.liveu.get_delay(&self.boss_id).await?;
+
+                if current_delay.delay == 1000 {
+                    (5000, t!("twitch.high_delay", locale = &self.lang))
+                } else {
+                    (1000, t!("twitch.low_delay", locale = &self.lang))
+                }
+            }
+        };
+
+        self.liveu.set_delay(&self.boss_id, delay.0).await?;
+        tokio::time::sleep(tokio::time::Duration::from_secs(2)).await;
+
+        if is_streaming {
+            self.generate_liveu_start_message(reply.clone())
+                .await?;
+        }
+
+        Ok(delay.1.to_string())
+    }
+
+    /// Turns a modem on or off, the port can be the custom name from `customPortNames`
+    async fn set_modem(&self, name: &str, enabled: bool) -> Result<String, Error> {
+        let custom_names = self.config.custom_port_names.clone().unwrap_or_default();
+
+        let port = match Liveu::custom_name_to_port(name, &custom_names) {
+            Some(port) => port,
+            None => return Ok(t!("twitch.unknown_modem", locale = &self.lang, port = name)),
+        };
+
+        let interface = self
+            .liveu
+            .get_interfaces(&self.boss_id)
+            .await?
+            .into_iter()
+            .find(|i| i.port == port);
+
+        let interface = match interface {
+            Some(interface) => interface,
+            None => return Ok(t!("twitch.unknown_modem", locale = &self.lang, port = name)),
+        };
+
+        if interface.enabled == enabled {
+            let key = if enabled { "twitch.modem_already_enabled" } else { "twitch.modem_already_disabled" };
+            return Ok(t!(key, locale = &self.lang, port = name));
+        }
+
+        if enabled {
+            self.liveu.enable_interface(&self.boss_id, port).await?;
+            Ok(t!("twitch.modem_enabled", locale = &self.lang, port = name))
+        } else {
+            self.liveu.disable_interface(&self.boss_id, port).await?;
+            Ok(t!("twitch.modem_disabled", locale = &self.lang, port = name))
+        }
+    }
+}
+
+/// Lowest delay in ms accepted by `!ludelay <delay>`
+pub const MIN_DELAY: u64 = 500;
+/// Highest delay in ms accepted by `!ludelay <delay>`
+pub const MAX_DELAY: u64 = 60000;
+
+/// What was typed after a command
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Args {
+    /// Name or id of the unit from `unit=<name>`
+    pub unit: Option<String>,
+    pub values: Vec<String>,
+}
+
+impl Args {
+    pub fn parse<'a>(words: impl Iterator<Item = &'a str>) -> Self {
+        let mut args = Args::default();
+
+        for word in words {
+            match word.split_once('=') {
+                Some((key, unit)) if key.eq_ignore_ascii_case("unit") && !unit.is_empty() => {
+                    args.unit = Some(unit.to_owned())
+                }
+                _ => args.values.push(word.to_owned()),
+            }
+        }
+
+        args
+    }
+
+    pub fn verbose(&self) -> bool {
+        self.values.iter().any(|v| v.eq_ignore_ascii_case("verbose"))
+    }
+
+    /// The delay in ms, only when it's in range
+    pub fn delay(&self) -> Option<u64> {
+        self.values
+            .first()
+            .and_then(|v| v.parse().ok())
+            .filter(|delay| (MIN_DELAY..=MAX_DELAY).contains(delay))
+    }
+
+    /// `on` or `off` after the port of `!lumodem <port> on|off`
+    pub fn modem_enabled(&self) -> Option<bool> {
+        match self.values.get(1).map(|v| v.to_lowercase()).as_deref() {
+            Some("on") => Some(true),
+            Some("off") => Some(false),
+            _ => None,
+        }
+    }
+
+    pub fn is_valid(&self, command: Command) -> bool {
+        match command {
+            Command::Stats => self.values.is_empty() || (self.values.len() == 1 && self.verbose()),
+            Command::Delay => self.values.is_empty() || (self.values.len() == 1 && self.delay().is_some()),
+            Command::Modem => self.values.len() == 2 && self.modem_enabled().is_some(),
+            _ => self.values.is_empty(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Command {
+    Stats,
+    Battery,
+    Start,
+    Stop,
+    Restart,
+    Reboot,
+    Delay,
+    Modem,
+    Srt,
+    Help,
+    /// Index in `commands.custom`
+    Custom(usize),
+    Unknown,
+}
+
+impl Command {
+    pub const ALL: [Command; 10] = [
+        Command::Stats,
+        Command::Battery,
+        Command::Start,
+        Command::Stop,
+        Command::Restart,
+        Command::Reboot,
+        Command::Delay,
+        Command::Modem,
+        Command::Srt,
+        Command::Help,
+    ];
+
+    /// The name of the command in the config
+    pub fn name(&self) -> &'static str {
+        match self {
+            Command::Stats => "stats",
+            Command::Battery => "battery",
+            Command::Start => "start",
+            Command::Stop => "stop",
+            Command::Restart => "restart",
+            Command::Reboot => "reboot",
+            Command::Delay => "delay",
+            Command::Modem => "modem",
+            Command::Srt => "srt",
+            Command::Help => "help",
+            Command::Custom(_) => "custom",
+            Command::Unknown => "unknown",
+        }
+    }
+
+    /// Commands that change something on the unit, these are audited
+    pub fn controls_unit(&self) -> bool {
+        matches!(
+            self,
+            Command::Start
+                | Command::Stop
+                | Command::Restart
+                | Command::Reboot
+                | Command::Delay
+                | Command::Modem
+        )
+    }
+
+    /// Gets a command by its name in the config, e.g. `stats`
+    pub fn from_name(name: &str) -> Command {
+        let name = name.to_lowercase();
+
+        Command::ALL
+            .into_iter()
+            .find(|command| command.name() == name)
+            .unwrap_or(Command::Unknown)
+    }
+}
+
+struct DataUsedInThread {
+    reply: Arc<dyn Reply>,
+    liveu: Liveu,
+    boss_id: String,
+    lang: String,
+    announce_success: bool,
+}
+
+impl DataUsedInThread {
+    async fn confirm_action(
+        &self,
+        max_attempts: u8,
+        should_have_bitrate: bool,
+        success: String,
+        not_success: String,
+    ) {
+        let mut attempts = 0;
+
+        while attempts != max_attempts {
+            tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
+
+            let video = self.liveu.get_video(&self.boss_id).await;
+
+            if let Ok(video) = video {
+                if video.bitrate.is_some() == should_have_bitrate {
+                    break;
+                }
+            }
+
+            attempts += 1;
+        }
+
+        if attempts == max_attempts {
+            let msg = t!(
+                "twitch.action_too_long", 
+                locale = &self.lang, 
+                not_success_msg = &not_success
+            );
+            self.reply.reply(msg).await;
+
+            return;
+        }
+
+        if !self.announce_success {
+            return;
+        }
+
+        let msg = t!("twitch.action_successfully", locale = &self.lang, success_msg = &success);
+        self.reply.reply(msg).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(text: &str) -> Args {
+        Args::parse(text.split_whitespace())
+    }
+
+    #[test]
+    fn parse_takes_the_unit_out_of_the_values() {
+        let parsed = args("SIM1 UNIT=backpack off");
+
+        assert_eq!(parsed.unit.as_deref(), Some("backpack"));
+        assert_eq!(parsed.values, vec!["SIM1", "off"]);
+
+        // an empty unit is just a value
+        assert_eq!(args("unit=").values, vec!["unit="]);
+    }
+
+    #[test]
+    fn delay_has_to_be_in_range() {
+        assert_eq!(args("500").delay(), Some(500));
+        assert_eq!(args("60000").delay(), Some(60000));
+        assert_eq!(args("499").delay(), None);
+        assert_eq!(args("60001").delay(), None);
+        assert_eq!(args("soon").delay(), None);
+
+        assert!(args("").is_valid(Command::Delay));
+        assert!(args("2000 unit=car").is_valid(Command::Delay));
+        assert!(!args("100").is_valid(Command::Delay));
+        assert!(!args("2000 3000").is_valid(Command::Delay));
+    }
+
+    #[test]
+    fn stats_only_takes_verbose() {
+        assert!(args("").is_valid(Command::Stats));
+        assert!(args("Verbose").is_valid(Command::Stats));
+        assert!(args("verbose unit=car").is_valid(Command::Stats));
+        assert!(!args("all").is_valid(Command::Stats));
+        assert!(!args("verbose verbose").is_valid(Command::Stats));
+    }
+
+    #[test]
+    fn modem_needs_a_port_and_on_or_off() {
+        assert_eq!(args("SIM1 ON").modem_enabled(), Some(true));
+        assert_eq!(args("SIM1 off").modem_enabled(), Some(false));
+        assert_eq!(args("SIM1 maybe").modem_enabled(), None);
+
+        assert!(args("SIM1 on").is_valid(Command::Modem));
+        assert!(!args("SIM1").is_valid(Command::Modem));
+        assert!(!args("on").is_valid(Command::Modem));
+        assert!(!args("SIM1 on now").is_valid(Command::Modem));
+    }
+
+    #[test]
+    fn other_commands_take_no_values() {
+        assert!(args("unit=car").is_valid(Command::Start));
+        assert!(!args("now").is_valid(Command::Reboot));
+    }
+}
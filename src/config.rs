@@ -1,9 +1,9 @@
 use error::Error;
 use read_input::prelude::*;
 use serde::{Deserialize, Serialize};
-use std::{fs, path::Path};
+use std::{collections::HashMap, fs, path::Path};
 
-use crate::{error, liveu};
+use crate::{chat::Role, error, events::Category, liveu};
 
 const CONFIG_FILE_NAME: &str = "config.json";
 
@@ -22,9 +22,41 @@ pub struct Monitor {
     pub battery: bool,
     pub battery_charging: bool,
     pub battery_notification: Vec<u8>,
+    /// Estimated minutes of battery left to notify at
+    #[serde(default)]
+    pub battery_time_notification: Vec<u32>,
     pub battery_interval: u64,
     pub modems: bool,
     pub modems_interval: u64,
+    /// Seconds a modem has to be gone before announcing it as disconnected
+    #[serde(default = "default_modems_grace_period")]
+    pub modems_grace_period: u64,
+    /// Seconds over which reconnects within the grace period are batched
+    #[serde(default = "default_modems_flap_window")]
+    pub modems_flap_window: u64,
+    /// Notify when a modem switches cellular technology, e.g. LTE to 3G
+    #[serde(default)]
+    pub modems_technology: bool,
+    /// Notify when a modem starts or stops roaming
+    #[serde(default)]
+    pub modems_roaming: bool,
+    /// Notify when the stream starts, stops or the unit goes offline
+    #[serde(default)]
+    pub stream: bool,
+    #[serde(default = "default_stream_interval")]
+    pub stream_interval: u64,
+}
+
+fn default_modems_grace_period() -> u64 {
+    30
+}
+
+fn default_modems_flap_window() -> u64 {
+    120
+}
+
+fn default_stream_interval() -> u64 {
+    5
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -35,19 +67,167 @@ pub struct Twitch {
     pub channel: String,
     pub admin_users: Option<Vec<String>>,
     pub mod_only: bool,
+    /// More channels to join besides `channel`
+    #[serde(default)]
+    pub channels: Vec<TwitchChannel>,
+    /// Refresh the token instead of using `botOauth`
+    pub auth: Option<TwitchAuth>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TwitchAuth {
+    pub client_id: String,
+    pub client_secret: String,
+    /// Only used the first time, the new tokens are saved in `tokenFile`
+    pub refresh_token: String,
+    /// Defaults to `twitch_token.json`
+    pub token_file: Option<String>,
+    /// Defaults to `https://id.twitch.tv/oauth2/token`
+    pub token_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TwitchChannel {
+    pub name: String,
+    /// Defaults to the `lang` of the config
+    pub lang: Option<String>,
+    /// Names of the enabled commands e.g. `["stats", "battery"]`, defaults to all
+    pub commands: Option<Vec<String>>,
+    /// Which notifications to send, defaults to all
+    pub events: Option<EventFilter>,
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub struct Commands {
+    /// Seconds, used by every command without its own cooldown
     pub cooldown: u16,
-    pub stats: Vec<String>,
-    pub battery: Vec<String>,
-    pub start: String,
-    pub stop: String,
-    pub restart: String,
-    pub reboot: String,
-    pub delay: String,
+    /// Seconds before the same user can use any command again
+    #[serde(default)]
+    pub user_cooldown: u16,
+    /// Tell the user when a command is on cooldown instead of ignoring it
+    #[serde(default)]
+    pub cooldown_reply: bool,
+    /// Seconds to confirm a command that needs confirmation
+    #[serde(default = "default_confirm_timeout")]
+    pub confirm_timeout: u16,
+    pub stats: CommandSettings,
+    pub battery: CommandSettings,
+    pub start: CommandSettings,
+    pub stop: CommandSettings,
+    pub restart: CommandSettings,
+    pub reboot: CommandSettings,
+    pub delay: CommandSettings,
+    #[serde(default = "default_modem")]
+    pub modem: CommandSettings,
+    #[serde(default = "default_srt")]
+    pub srt: CommandSettings,
+    #[serde(default = "default_help")]
+    pub help: CommandSettings,
+    #[serde(default)]
+    pub custom: Vec<CustomCommand>,
+}
+
+/// A command that answers with a template, e.g. `"Bitrate: {total_lrt} Kbps"`
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct CustomCommand {
+    #[serde(flatten)]
+    pub command: CommandSettings,
+    pub response: String,
+    /// Replaces the technology of the modems in `{modems}`, e.g. `{"LTE": "📶"}`
+    #[serde(default)]
+    pub technologies: HashMap<String, String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase", from = "CommandEntry")]
+pub struct CommandSettings {
+    pub triggers: Vec<String>,
+    pub enabled: bool,
+    /// Lowest role allowed to use the command, defaults to everyone for stats and battery
+    /// and to admin for the others
+    pub role: Option<Role>,
+    /// Seconds, defaults to the `cooldown` of all commands
+    pub cooldown: Option<u16>,
+    /// The command only runs after typing it again followed by `confirm`
+    pub confirm: bool,
+}
+
+impl CommandSettings {
+    pub fn new(triggers: &[&str]) -> Self {
+        Self {
+            triggers: triggers.iter().map(|t| t.to_string()).collect(),
+            enabled: true,
+            role: None,
+            cooldown: None,
+            confirm: false,
+        }
+    }
+}
+
+/// Older configs only have the triggers of a command
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum CommandEntry {
+    Trigger(String),
+    Triggers(Vec<String>),
+    Settings {
+        triggers: Vec<String>,
+        #[serde(default = "default_enabled")]
+        enabled: bool,
+        role: Option<Role>,
+        cooldown: Option<u16>,
+        #[serde(default)]
+        confirm: bool,
+    },
+}
+
+impl From<CommandEntry> for CommandSettings {
+    fn from(entry: CommandEntry) -> Self {
+        match entry {
+            CommandEntry::Trigger(trigger) => Self::new(&[&trigger]),
+            CommandEntry::Triggers(triggers) => Self {
+                triggers,
+                ..Self::new(&[])
+            },
+            CommandEntry::Settings {
+                triggers,
+                enabled,
+                role,
+                cooldown,
+                confirm,
+            } => Self {
+                triggers,
+                enabled,
+                role,
+                cooldown,
+                confirm,
+            },
+        }
+    }
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+fn default_modem() -> CommandSettings {
+    CommandSettings::new(&["!lumodem"])
+}
+
+fn default_srt() -> CommandSettings {
+    CommandSettings::new(&["!lusrt"])
+}
+
+fn default_help() -> CommandSettings {
+    CommandSettings::new(&["!luhelp"])
+}
+
+fn default_confirm_timeout() -> u16 {
+    15
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
@@ -61,19 +241,184 @@ pub struct Rtmp {
 pub struct Srt {
     pub url: String,
     pub publisher: String,
+    /// The server the stats page belongs to
+    #[serde(default)]
+    pub kind: SrtKind,
+    /// How often in ms to get the stats
+    #[serde(default = "default_srt_poll_interval")]
+    pub poll_interval: u64,
+    /// Warn about packet loss, drops and RTT of the publisher
+    #[serde(default)]
+    pub alerts: Option<SrtAlerts>,
+}
+
+/// The formats of the srt stats pages
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SrtKind {
+    /// The `/stats` page of the srt-live-server of BELABOX and IRLToolkit,
+    /// `publishers.<publisher>` in camelCase
+    #[default]
+    #[serde(alias = "belabox")]
+    Sls,
+    /// `/v3/srtconns/list` of the MediaMTX api, the publisher is the path
+    Mediamtx,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SrtAlerts {
+    /// Seconds over which the loss and drops are counted
+    #[serde(default = "default_srt_alerts_interval")]
+    pub interval: u64,
+    /// Lost packets per second
+    pub loss_rate: Option<f64>,
+    /// Dropped packets per second
+    pub drop_rate: Option<f64>,
+    /// Round trip time in ms
+    pub rtt: Option<f64>,
+    /// Intervals without problems before the link counts as stable again
+    #[serde(default = "default_srt_recovery_intervals")]
+    pub recovery_intervals: u32,
+}
+
+fn default_srt_poll_interval() -> u64 {
+    1000
+}
+
+fn default_srt_alerts_interval() -> u64 {
+    10
+}
+
+fn default_srt_recovery_intervals() -> u32 {
+    3
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Discord {
+    pub webhook_url: String,
+    pub events: EventFilter,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Webhook {
+    pub url: String,
+    pub events: EventFilter,
+    /// Signs the body with HMAC-SHA256 when set
+    pub secret: Option<String>,
+    #[serde(default = "default_webhook_retries")]
+    pub retries: u8,
+    /// Seconds to wait before the first retry, doubled for every retry after that
+    #[serde(default = "default_webhook_retry_delay")]
+    pub retry_delay: u64,
+}
+
+fn default_webhook_retries() -> u8 {
+    3
+}
+
+fn default_webhook_retry_delay() -> u64 {
+    5
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Telegram {
+    pub bot_token: String,
+    /// Chats that receive the notifications and are allowed to use every command
+    pub chat_ids: Vec<i64>,
+    pub events: EventFilter,
+    /// Use a different Bot API server, e.g. a local one
+    pub api_url: Option<String>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Mqtt {
+    pub host: String,
+    pub port: Option<u16>,
+    pub client_id: Option<String>,
+    pub username: Option<String>,
+    pub password: Option<String>,
+    /// Defaults to `liveu`
+    pub topic_prefix: Option<String>,
+    /// Name of the unit in the topics, defaults to the boss id
+    pub unit: Option<String>,
+    /// Listen for commands on `<topicPrefix>/<unit>/command`
+    pub commands: bool,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Youtube {
+    pub client_id: String,
+    pub client_secret: String,
+    /// OAuth refresh token of the channel the bot talks as
+    pub refresh_token: String,
+    /// Live stream to join, defaults to the active broadcast of the channel
+    pub video_id: Option<String>,
+    /// Channel ids, e.g. `UC...`
+    pub admin_users: Option<Vec<String>>,
+    pub mod_only: bool,
+    /// Seconds between reading the chat, defaults to 20
+    pub poll_interval: Option<u64>,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Kick {
+    pub chatroom_id: u64,
+    pub broadcaster_user_id: u64,
+    /// User access token with the `chat:write` scope
+    pub access_token: String,
+    pub admin_users: Option<Vec<String>>,
+    pub mod_only: bool,
+    pub pusher_url: Option<String>,
+    pub api_url: Option<String>,
+}
+
+/// Which kind of events to send
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct EventFilter {
+    pub modems: bool,
+    pub battery: bool,
+    pub stream: bool,
+    pub commands: bool,
+    #[serde(default = "default_enabled")]
+    pub srt: bool,
+}
+
+impl EventFilter {
+    pub fn allows(&self, category: Category) -> bool {
+        match category {
+            Category::Modems => self.modems,
+            Category::Battery => self.battery,
+            Category::Stream => self.stream,
+            Category::Command => self.commands,
+            Category::Srt => self.srt,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Config {
     pub liveu: Liveu,
-    pub twitch: Twitch,
+    pub twitch: Option<Twitch>,
     pub commands: Commands,
     pub rtmp: Option<Rtmp>,
     pub srt: Option<Srt>,
     pub server: bool,
     pub lang: String,
     pub custom_port_names: Option<CustomUnitNames>,
+    pub discord: Option<Discord>,
+    pub webhooks: Option<Vec<Webhook>>,
+    pub telegram: Option<Telegram>,
+    pub mqtt: Option<Mqtt>,
+    pub youtube: Option<Youtube>,
+    pub kick: Option<Kick>,
 }
 
 impl Config {
@@ -89,6 +434,12 @@ impl Config {
         Ok(config)
     }
 
+    /// Whether the modems and battery are polled in the background
+    /// for something other than the chat notifications
+    pub fn polls_stats(&self) -> bool {
+        self.server || self.mqtt.is_some()
+    }
+
     /// Lowercase settings which should always be lowercase
     pub fn lowercase_settings(config: &mut Config) {
         let Twitch {
@@ -96,13 +447,21 @@ impl Config {
             bot_oauth,
             channel,
             admin_users,
+            channels,
             ..
-        } = &mut config.twitch;
+        } = match &mut config.twitch {
+            Some(twitch) => twitch,
+            None => return,
+        };
 
         *channel = channel.to_lowercase();
         *bot_oauth = bot_oauth.to_lowercase();
         *bot_username = bot_username.to_lowercase();
 
+        for channel in channels {
+            channel.name = channel.name.to_lowercase();
+        }
+
         if let Some(admin_users) = admin_users {
             for user in admin_users {
                 *user = user.to_lowercase();
@@ -126,9 +485,16 @@ impl Config {
         let monitor = Monitor {
             battery: monitor_enabled,
             battery_notification: [99, 50, 10, 5, 1].to_vec(),
+            battery_time_notification: [30, 15, 5].to_vec(),
             modems: monitor_enabled,
             battery_interval: 10,
             modems_interval: 10,
+            modems_grace_period: default_modems_grace_period(),
+            modems_flap_window: default_modems_flap_window(),
+            modems_technology: monitor_enabled,
+            modems_roaming: monitor_enabled,
+            stream: monitor_enabled,
+            stream_interval: default_stream_interval(),
             battery_charging: monitor_enabled,
         };
 
@@ -172,23 +538,36 @@ impl Config {
             }
         }
 
-        println!("\nPlease enter your Twitch details below");
-        let twitch = Twitch {
-            bot_username: input().msg("Bot username: ").get(),
-            bot_oauth: input()
-                .msg("(You can generate an Oauth here: https://twitchapps.com/tmi/)\nBot oauth: ")
-                .get(),
-            channel: input().msg("Channel name: ").get(),
-            admin_users: None,
-            mod_only: input_to_bool(
-                &input()
-                    .msg("Only allow mods to access the commands (Y/n): ")
-                    .add_test(|x: &String| x.to_lowercase() == "y" || x.to_lowercase() == "n")
-                    .err("Please enter y or n: ")
-                    .default("y".to_string())
+        let q: String = input()
+            .msg("\nWould you like to use the bot in your Twitch chat (Y/n): ")
+            .add_test(|x: &String| x.to_lowercase() == "y" || x.to_lowercase() == "n")
+            .err("Please enter y or n: ")
+            .default("y".to_string())
+            .get();
+
+        let mut twitch = None;
+
+        if q == "y" {
+            println!("\nPlease enter your Twitch details below");
+            twitch = Some(Twitch {
+                bot_username: input().msg("Bot username: ").get(),
+                bot_oauth: input()
+                    .msg("(You can generate an Oauth here: https://twitchapps.com/tmi/)\nBot oauth: ")
                     .get(),
-            ),
-        };
+                channel: input().msg("Channel name: ").get(),
+                admin_users: None,
+                channels: Vec::new(),
+                auth: None,
+                mod_only: input_to_bool(
+                    &input()
+                        .msg("Only allow mods to access the commands (Y/n): ")
+                        .add_test(|x: &String| x.to_lowercase() == "y" || x.to_lowercase() == "n")
+                        .err("Please enter y or n: ")
+                        .default("y".to_string())
+                        .get(),
+                ),
+            });
+        }
 
         let commands = Commands {
             cooldown: input()
@@ -196,21 +575,20 @@ impl Config {
                 .err("Please enter a number")
                 .default(5)
                 .get(),
-            stats: vec![
-                "!lustats".to_string(),
-                "!liveustats".to_string(),
-                "!lus".to_string(),
-            ],
-            battery: vec![
-                "!battery".to_string(),
-                "!liveubattery".to_string(),
-                "!lub".to_string(),
-            ],
-            start: "!lustart".to_string(),
-            stop: "!lustop".to_string(),
-            restart: "!lurestart".to_string(),
-            reboot: "!lureboot".to_string(),
-            delay: "!ludelay".to_string(),
+            user_cooldown: 0,
+            cooldown_reply: false,
+            confirm_timeout: default_confirm_timeout(),
+            stats: CommandSettings::new(&["!lustats", "!liveustats", "!lus"]),
+            battery: CommandSettings::new(&["!battery", "!liveubattery", "!lub"]),
+            start: CommandSettings::new(&["!lustart"]),
+            stop: CommandSettings::new(&["!lustop"]),
+            restart: CommandSettings::new(&["!lurestart"]),
+            reboot: CommandSettings::new(&["!lureboot"]),
+            delay: CommandSettings::new(&["!ludelay"]),
+            modem: default_modem(),
+            srt: default_srt(),
+            help: default_help(),
+            custom: Vec::new(),
         };
 
         let q: String = input()
@@ -226,6 +604,9 @@ impl Config {
             srt = Some(Srt {
                 url: input().msg("Please enter the stats page URL: ").get(),
                 publisher: input().msg("Publisher: ").get(),
+                kind: SrtKind::Sls,
+                poll_interval: default_srt_poll_interval(),
+                alerts: None,
             });
         }
 
@@ -279,6 +660,12 @@ impl Config {
             server,
             lang,
             custom_port_names: custom_unit_names,
+            discord: None,
+            webhooks: None,
+            telegram: None,
+            mqtt: None,
+            youtube: None,
+            kick: None,
         };
         fs::write(CONFIG_FILE_NAME, serde_json::to_string_pretty(&config)?)?;
 
@@ -330,3 +717,72 @@ fn input_to_bool(confirm: &str) -> bool {
 
     false
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn settings(json: &str) -> CommandSettings {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn command_from_a_single_trigger() {
+        assert_eq!(settings(r#""!lustart""#), CommandSettings::new(&["!lustart"]));
+    }
+
+    #[test]
+    fn command_from_a_list_of_triggers() {
+        assert_eq!(
+            settings(r#"["!lus", "!lustats"]"#),
+            CommandSettings::new(&["!lus", "!lustats"])
+        );
+    }
+
+    #[test]
+    fn command_from_settings() {
+        let command = settings(
+            r#"{
+                "triggers": ["!lureboot"],
+                "enabled": false,
+                "role": "mod",
+                "cooldown": 60,
+                "confirm": true
+            }"#,
+        );
+
+        assert_eq!(
+            command,
+            CommandSettings {
+                triggers: vec!["!lureboot".to_string()],
+                enabled: false,
+                role: Some(Role::Moderator),
+                cooldown: Some(60),
+                confirm: true,
+            }
+        );
+
+        let command = settings(r#"{ "triggers": ["!lus"] }"#);
+        assert_eq!(command, CommandSettings::new(&["!lus"]));
+    }
+
+    #[test]
+    fn custom_command_with_role_and_confirm() {
+        let command: CustomCommand = serde_json::from_str(
+            r#"{
+                "triggers": ["!lubitrate"],
+                "role": "vip",
+                "confirm": true,
+                "response": "Bitrate: {total_lrt} Kbps"
+            }"#,
+        )
+        .unwrap();
+
+        assert_eq!(command.command.triggers, vec!["!lubitrate".to_string()]);
+        assert!(command.command.enabled);
+        assert_eq!(command.command.role, Some(Role::Vip));
+        assert!(command.command.confirm);
+        assert_eq!(command.response, "Bitrate: {total_lrt} Kbps");
+        assert!(command.technologies.is_empty());
+    }
+}
@@ -0,0 +1,105 @@
+use rust_i18n::t;
+use serde::Deserialize;
+use serde_json::{json, Value};
+use tokio::sync::broadcast;
+
+use crate::{
+    config,
+    error::Error,
+    events::{Category, Event},
+};
+
+#[derive(Deserialize, Debug)]
+struct RateLimited {
+    retry_after: f64,
+}
+
+/// Posts every event allowed by the config to the Discord webhook
+pub async fn run(config: config::Discord, lang: String, mut events: broadcast::Receiver<Event>) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        if !config.events.allows(event.category()) {
+            continue;
+        }
+
+        if let Err(e) = send_event(&config.webhook_url, &event, &lang).await {
+            println!("Discord: failed to send notification: {}", e);
+        }
+    }
+}
+
+async fn send_event(webhook_url: &str, event: &Event, lang: &str) -> Result<(), Error> {
+    let payload = json!({ "embeds": [generate_embed(event, lang)] });
+    let client = reqwest::Client::new();
+
+    let res = client.post(webhook_url).json(&payload).send().await?;
+
+    // Discord tells us how long to wait when we are sending too much
+    if res.status() == reqwest::StatusCode::TOO_MANY_REQUESTS {
+        let limit: RateLimited = res.json().await?;
+        tokio::time::sleep(tokio::time::Duration::from_secs_f64(limit.retry_after)).await;
+        client
+            .post(webhook_url)
+            .json(&payload)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        return Ok(());
+    }
+
+    res.error_for_status()?;
+
+    Ok(())
+}
+
+fn generate_embed(event: &Event, lang: &str) -> Value {
+    let color = match event {
+        Event::ModemsChanged { removed, .. } if !removed.is_empty() => 0xe67e22,
+        Event::ChargingChanged { charging: false }
+        | Event::TooHot
+        | Event::BatteryThreshold { .. }
+        | Event::BatteryTimeThreshold { .. }
+        | Event::UnitOffline
+        | Event::SrtUnstable { .. } => 0xe74c3c,
+        Event::StreamStarted | Event::UnitOnline | Event::FullyCharged | Event::SrtStable => 0x2ecc71,
+        _ => match event.category() {
+            Category::Command => 0x9b59b6,
+            _ => 0x3498db,
+        },
+    };
+
+    let mut embed = json!({
+        "title": "LiveU",
+        "description": event.message(lang),
+        "color": color,
+    });
+
+    if let Event::ModemsChanged { modems, .. } = event {
+        let fields: Vec<Value> = modems
+            .iter()
+            .map(|modem| {
+                let mut value = format!("{} Kbps", modem.uplink_kbps);
+
+                if !modem.technology.is_empty() {
+                    value += &format!(" ({})", modem.technology);
+                }
+
+                if modem.is_currently_roaming {
+                    value += &t!("twitch.roaming", locale = lang);
+                }
+
+                json!({ "name": modem.port, "value": value, "inline": true })
+            })
+            .collect();
+
+        embed["fields"] = Value::Array(fields);
+    }
+
+    embed
+}
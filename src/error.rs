@@ -31,6 +31,21 @@ pub enum Error {
     #[error("Status not available")]
     StatusNotAvailable,
 
+    #[error("Telegram error: {0}")]
+    Telegram(String),
+
+    #[error("Twitch login failed: {0}")]
+    TwitchLogin(String),
+
+    #[error("Websocket error: {0}")]
+    Websocket(Box<tokio_tungstenite::tungstenite::Error>),
+
     #[error("Not enough permissions to use command")]
     NotEnoughPermissions,
 }
+
+impl From<tokio_tungstenite::tungstenite::Error> for Error {
+    fn from(e: tokio_tungstenite::tungstenite::Error) -> Self {
+        Error::Websocket(Box::new(e))
+    }
+}
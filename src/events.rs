@@ -0,0 +1,223 @@
+use rust_i18n::t;
+use serde::Serialize;
+
+use crate::liveu_monitor::{technology_rank, Modem, ModemFlap};
+
+/// Everything the monitor can notify about
+#[derive(Debug, Clone, Serialize)]
+#[serde(tag = "type", content = "data", rename_all = "snake_case")]
+pub enum Event {
+    ModemsChanged {
+        added: Vec<String>,
+        removed: Vec<String>,
+        flapped: Vec<ModemFlap>,
+        modems: Vec<Modem>,
+    },
+    TechnologyChanged {
+        port: String,
+        from: String,
+        to: String,
+    },
+    RoamingChanged {
+        port: String,
+        roaming: bool,
+    },
+    BatteryThreshold {
+        percentage: u8,
+        charging: bool,
+    },
+    BatteryTimeThreshold {
+        minutes: u32,
+    },
+    ChargingChanged {
+        charging: bool,
+    },
+    TooHot,
+    FullyCharged,
+    StreamStarted,
+    StreamStopped,
+    UnitOnline,
+    UnitOffline,
+    CommandExecuted {
+        command: String,
+        user: String,
+        channel: String,
+    },
+    SrtUnstable {
+        loss_rate: f64,
+        drop_rate: f64,
+        rtt: f64,
+    },
+    SrtStable,
+}
+
+/// The kind of an event, used to choose where events go
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Category {
+    Modems,
+    Battery,
+    Stream,
+    Command,
+    Srt,
+}
+
+impl Event {
+    pub fn category(&self) -> Category {
+        match self {
+            Event::ModemsChanged { .. }
+            | Event::TechnologyChanged { .. }
+            | Event::RoamingChanged { .. } => Category::Modems,
+            Event::BatteryThreshold { .. }
+            | Event::BatteryTimeThreshold { .. }
+            | Event::ChargingChanged { .. }
+            | Event::TooHot
+            | Event::FullyCharged => Category::Battery,
+            Event::StreamStarted
+            | Event::StreamStopped
+            | Event::UnitOnline
+            | Event::UnitOffline => Category::Stream,
+            Event::CommandExecuted { .. } => Category::Command,
+            Event::SrtUnstable { .. } | Event::SrtStable => Category::Srt,
+        }
+    }
+
+    /// The chat message for this event
+    pub fn message(&self, lang: &str) -> String {
+        match self {
+            Event::ModemsChanged {
+                added,
+                removed,
+                flapped,
+                ..
+            } => "LiveU: ".to_string() + &Self::generate_modems_message(added, removed, flapped, lang),
+            Event::TechnologyChanged { port, from, to } => {
+                let key = if technology_rank(to) < technology_rank(from) {
+                    "monitor.technology_downgrade"
+                } else {
+                    "monitor.technology_upgrade"
+                };
+
+                t!(key, locale = lang, modem = port, from = from, to = to)
+            }
+            Event::RoamingChanged { port, roaming: true } => {
+                t!("monitor.roaming_started", locale = lang, modem = port)
+            }
+            Event::RoamingChanged { port, roaming: false } => {
+                t!("monitor.roaming_stopped", locale = lang, modem = port)
+            }
+            Event::BatteryThreshold { percentage, charging } => {
+                let a = if *charging { t!("monitor.charging", locale = lang)} else { t!("monitor.not_charging", locale = lang)};
+
+                t!(
+                    "monitor.battery_percentage",
+                    locale = lang,
+                    percent = &percentage.to_string(),
+                    chargingORnot = &a
+                )
+            }
+            Event::BatteryTimeThreshold { minutes } => t!(
+                "monitor.battery_time",
+                locale = lang,
+                minutes = &minutes.to_string()
+            ),
+            Event::ChargingChanged { charging: true } => t!("monitor.now_charging", locale = lang),
+            Event::ChargingChanged { charging: false } => t!("monitor.rip_power", locale = lang),
+            Event::TooHot => t!("monitor.too_hot", locale = lang),
+            Event::FullyCharged => t!("monitor.fully_charged", locale = lang),
+            Event::StreamStarted => t!(
+                "twitch.action_successfully",
+                locale = lang,
+                success_msg = &t!("twitch.started", locale = lang)
+            ),
+            Event::StreamStopped => t!(
+                "twitch.action_successfully",
+                locale = lang,
+                success_msg = &t!("twitch.stopped", locale = lang)
+            ),
+            Event::UnitOnline => t!("twitch.online_ready", locale = lang),
+            Event::UnitOffline => t!("twitch.offline", locale = lang),
+            Event::CommandExecuted {
+                command,
+                user,
+                channel,
+            } => t!(
+                "monitor.command_executed",
+                locale = lang,
+                user = user,
+                command = command,
+                channel = channel
+            ),
+            Event::SrtUnstable {
+                loss_rate,
+                drop_rate,
+                rtt,
+            } => t!(
+                "monitor.srt_unstable",
+                locale = lang,
+                loss = &format!("{:.1}", loss_rate),
+                drop = &format!("{:.1}", drop_rate),
+                rtt = &format!("{:.0}", rtt)
+            ),
+            Event::SrtStable => t!("monitor.srt_stable", locale = lang),
+        }
+    }
+
+    fn generate_modems_message(
+        new_modems: &[String],
+        removed_modems: &[String],
+        flapped_modems: &[ModemFlap],
+        lang: &str,
+    ) -> String {
+        let mut message = String::new();
+
+        if !new_modems.is_empty() {
+            let a = if new_modems.len() > 1 { t!("monitor.are", locale = lang) } else { t!("monitor.is", locale = lang) };
+
+            message += t!(
+                "monitor.new_modem",
+                locale = lang,
+                newModems = &new_modems.join(", "),
+                isORare = &a
+            ).as_str();
+        }
+
+        if !removed_modems.is_empty() {
+            if !message.is_empty() {
+                message += ", ";
+            }
+
+            let a = if removed_modems.len() > 1 {
+                t!("monitor.have", locale = lang)
+            } else {
+                t!("monitor.has", locale = lang)
+            };
+
+            message += t!(
+                "monitor.remove_modem",
+                locale = lang,
+                removedModems = &removed_modems.join(", "),
+                haveORhas = &a
+            ).as_str();
+        }
+
+        for flap in flapped_modems {
+            if !message.is_empty() {
+                message += ", ";
+            }
+
+            // round up so flapping for less than a minute still reads sensibly
+            let minutes = flap.window.as_secs().div_ceil(60);
+
+            message += t!(
+                "monitor.flapped_modem",
+                locale = lang,
+                modem = &flap.port,
+                count = &flap.count.to_string(),
+                minutes = &minutes.to_string()
+            ).as_str();
+        }
+
+        message
+    }
+}
@@ -0,0 +1,190 @@
+use std::time::Duration;
+
+use async_trait::async_trait;
+use futures_util::{SinkExt, StreamExt};
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::mpsc;
+use tokio_tungstenite::tungstenite::Message;
+
+use crate::{
+    chat::{ChatMessage, ChatPlatform, Role},
+    config,
+    error::Error,
+};
+
+const KICK_API: &str = "https://api.kick.com";
+
+/// The public Pusher app the Kick website reads chat from
+const KICK_PUSHER: &str =
+    "wss://ws-us2.pusher.com/app/32cbd69e4b950bf97679?protocol=7&client=js&version=8.4.0-rc2&flash=false";
+
+const CHAT_MESSAGE_EVENT: &str = "App\\Events\\ChatMessageEvent";
+
+#[derive(Deserialize, Debug)]
+struct PusherEvent {
+    event: String,
+    /// Pusher sends the data as a JSON encoded string
+    data: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KickMessage {
+    content: String,
+    sender: Sender,
+}
+
+#[derive(Deserialize, Debug)]
+struct Sender {
+    username: String,
+    identity: Identity,
+}
+
+#[derive(Deserialize, Debug)]
+struct Identity {
+    badges: Vec<KickBadge>,
+}
+
+#[derive(Deserialize, Debug)]
+struct KickBadge {
+    #[serde(rename = "type")]
+    kind: String,
+}
+
+pub struct Kick {
+    config: config::Kick,
+    client: reqwest::Client,
+}
+
+impl Kick {
+    pub fn new(config: config::Kick) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Reads the chat until the websocket closes
+    async fn read_chat(&self, messages: &mpsc::UnboundedSender<ChatMessage>) -> Result<(), Error> {
+        let url = self.config.pusher_url.as_deref().unwrap_or(KICK_PUSHER);
+        let (mut socket, _) = tokio_tungstenite::connect_async(url).await?;
+
+        let subscribe = json!({
+            "event": "pusher:subscribe",
+            "data": { "auth": "", "channel": format!("chatrooms.{}.v2", self.config.chatroom_id) },
+        });
+        socket.send(Message::Text(subscribe.to_string())).await?;
+
+        println!("Kick: Connected");
+
+        while let Some(message) = socket.next().await {
+            let text = match message? {
+                Message::Text(text) => text,
+                Message::Close(_) => break,
+                _ => continue,
+            };
+
+            let event: PusherEvent = match serde_json::from_str(&text) {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+
+            match event.event.as_str() {
+                "pusher:ping" => {
+                    let pong = json!({ "event": "pusher:pong", "data": {} });
+                    socket.send(Message::Text(pong.to_string())).await?;
+                }
+                CHAT_MESSAGE_EVENT => {
+                    let message: KickMessage =
+                        match serde_json::from_str(&event.data.unwrap_or_default()) {
+                            Ok(message) => message,
+                            Err(_) => continue,
+                        };
+
+                    let _ = messages.send(ChatMessage {
+                        channel: self.config.chatroom_id.to_string(),
+                        role: self.get_role(&message.sender),
+                        user: message.sender.username,
+                        text: message.content,
+                    });
+                }
+                _ => {}
+            }
+        }
+
+        Ok(())
+    }
+
+    fn get_role(&self, sender: &Sender) -> Role {
+        let has_badge = |name: &str| sender.identity.badges.iter().any(|b| b.kind == name);
+
+        let is_admin = self
+            .config
+            .admin_users
+            .iter()
+            .flatten()
+            .any(|user| user.to_lowercase() == sender.username.to_lowercase());
+
+        if has_badge("broadcaster") {
+            Role::Broadcaster
+        } else if is_admin {
+            Role::Admin
+        } else if has_badge("moderator") {
+            Role::Moderator
+        } else if has_badge("vip") {
+            Role::Vip
+        } else if has_badge("subscriber") || has_badge("founder") || has_badge("og") {
+            Role::Subscriber
+        } else {
+            Role::Everyone
+        }
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for Kick {
+    fn name(&self) -> &'static str {
+        "kick"
+    }
+
+    fn mod_only(&self) -> bool {
+        self.config.mod_only
+    }
+
+    async fn channels(&self) -> Vec<String> {
+        vec![self.config.chatroom_id.to_string()]
+    }
+
+    /// Kick only has the one chatroom of the broadcaster, so the channel isn't needed
+    async fn send(&self, _channel: &str, message: String) {
+        let api = self.config.api_url.as_deref().unwrap_or(KICK_API);
+
+        let body = json!({
+            "broadcaster_user_id": self.config.broadcaster_user_id,
+            "content": message,
+            "type": "user",
+        });
+
+        let res = self
+            .client
+            .post(format!("{}/public/v1/chat", api))
+            .bearer_auth(&self.config.access_token)
+            .json(&body)
+            .send()
+            .await;
+
+        if let Err(e) = res.and_then(|r| r.error_for_status()) {
+            println!("Kick: failed to send message: {}", e);
+        }
+    }
+
+    async fn receive(&self, messages: mpsc::UnboundedSender<ChatMessage>) -> Result<(), Error> {
+        loop {
+            if let Err(e) = self.read_chat(&messages).await {
+                println!("Kick: Connection error: {}", e);
+            }
+
+            tokio::time::sleep(Duration::from_secs(5)).await;
+        }
+    }
+}
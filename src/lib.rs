@@ -1,9 +1,19 @@
+pub mod chat;
+pub mod commands;
 pub mod config;
+pub mod discord;
 pub mod error;
+pub mod events;
+pub mod kick;
 pub mod liveu;
 pub mod liveu_monitor;
+pub mod mqtt;
 pub mod nginx;
 pub mod srt;
+pub mod telegram;
 pub mod twitch;
+pub mod twitch_auth;
+pub mod webhook;
+pub mod youtube;
 
 rust_i18n::i18n!("locales");
\ No newline at end of file
@@ -30,7 +30,6 @@ struct Data {
 #[derive(Deserialize, Debug, Clone)]
 struct AuthRes {
     access_token: String,
-    expires_in: u64,
 }
 
 #[derive(Deserialize, Debug)]
@@ -98,6 +97,12 @@ pub struct Delay {
     pub delay: u64,
 }
 
+#[derive(Serialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct InterfaceReq {
+    pub enabled: bool,
+}
+
 #[derive(Debug, Clone)]
 pub struct Liveu {
     access_token: Arc<Mutex<String>>,
@@ -368,6 +373,35 @@ impl Liveu {
         }
     }
 
+    /// Turns a single modem of the unit on or off, `port` is the name the unit uses e.g. `wlan0`
+    pub async fn set_interface_enabled(
+        &self,
+        boss_id: &str,
+        port: &str,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        let res = self
+            .send_request(
+                Method::PUT,
+                &format!("{}/units/{}/interfaces/{}", LIVEU_API, &boss_id, port),
+                Some(InterfaceReq { enabled }),
+            )
+            .await?;
+
+        match res.status() {
+            StatusCode::OK | StatusCode::NO_CONTENT => Ok(()),
+            _ => Err(Error::StatusNotAvailable),
+        }
+    }
+
+    pub async fn enable_interface(&self, boss_id: &str, port: &str) -> Result<(), Error> {
+        self.set_interface_enabled(boss_id, port, true).await
+    }
+
+    pub async fn disable_interface(&self, boss_id: &str, port: &str) -> Result<(), Error> {
+        self.set_interface_enabled(boss_id, port, false).await
+    }
+
     /// Gets the location of the boss_id in the inventories
     pub fn get_boss_id_location(inventories: &Inventories) -> usize {
         let size = inventories.units.len();
@@ -419,28 +453,36 @@ impl Liveu {
         mut interface: Interface,
         custom_names: &config::CustomUnitNames,
     ) -> Interface {
-        match interface.port.as_ref() {
-            "eth0" => {
-                interface.port = custom_names.ethernet.to_string();
-            }
-            "wlan0" => {
-                interface.port = custom_names.wifi.to_string();
-            }
-            "0" => {
-                interface.port = custom_names.sim1.to_string();
-            }
-            "1" => {
-                interface.port = custom_names.sim2.to_string();
-            }
-            "2" => {
-                interface.port = custom_names.usb1.to_string();
-            }
-            "3" => {
-                interface.port = custom_names.usb2.to_string();
-            }
-            _ => {}
+        if let Some((_, custom)) = Self::port_names(custom_names)
+            .into_iter()
+            .find(|(port, _)| *port == interface.port)
+        {
+            interface.port = custom.to_string();
         }
 
         interface
     }
+
+    /// Gets the port the unit uses from a custom name, the port itself is accepted as well
+    pub fn custom_name_to_port(
+        name: &str,
+        custom_names: &config::CustomUnitNames,
+    ) -> Option<&'static str> {
+        Self::port_names(custom_names)
+            .into_iter()
+            .find(|(port, custom)| port.eq_ignore_ascii_case(name) || custom.eq_ignore_ascii_case(name))
+            .map(|(port, _)| port)
+    }
+
+    /// The ports the unit uses and their custom names
+    fn port_names(custom_names: &config::CustomUnitNames) -> [(&'static str, &str); 6] {
+        [
+            ("eth0", &custom_names.ethernet),
+            ("wlan0", &custom_names.wifi),
+            ("0", &custom_names.sim1),
+            ("1", &custom_names.sim2),
+            ("2", &custom_names.usb1),
+            ("3", &custom_names.usb2),
+        ]
+    }
 }
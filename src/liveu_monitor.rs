@@ -1,15 +1,13 @@
-use std::sync::Arc;
-
-use tokio::sync::Mutex;
-use twitch_irc::{
-    login,
-    transport::tcp::{TCPTransport, TLS},
-    TwitchIRCClient,
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
 };
+
+use tokio::sync::{broadcast, Mutex};
 use serde::Serialize;
-use rust_i18n::t;
 
-use crate::{config, liveu};
+use crate::{config, error::Error, events::Event, liveu};
 
 #[derive(Debug, Clone, Serialize)]
 pub struct Modem {
@@ -23,7 +21,6 @@ pub struct Modem {
 
 #[derive(Debug, Clone)]
 pub struct Monitor {
-    pub client: TwitchIRCClient<TCPTransport<TLS>, login::StaticLoginCredentials>,
     pub config: config::Config,
     pub liveu: liveu::Liveu,
     pub boss_id: String,
@@ -31,6 +28,7 @@ pub struct Monitor {
     pub total_bitrate: Arc<Mutex<u32>>,
     pub modem_sync: Arc<Mutex<Vec<Modem>>>,
     pub battery_sync: Arc<Mutex<liveu::Battery>>,
+    pub events: broadcast::Sender<Event>,
 }
 
 impl Monitor {
@@ -40,20 +38,28 @@ impl Monitor {
 
         let battery = self.clone();
         tokio::spawn(async move { battery.monitor_battery().await });
+
+        let stream = self.clone();
+        tokio::spawn(async move { stream.monitor_stream().await });
     }
 
     pub async fn monitor_modems(&self) {
-        let mut current_modems = Vec::new();
+        let mut tracker = ModemTracker::new(
+            Duration::from_secs(self.config.liveu.monitor.modems_grace_period),
+            Duration::from_secs(self.config.liveu.monitor.modems_flap_window),
+        );
+        let mut links = LinkTracker::default();
         let mut ignore = false;
 
-        for interface in self
+        let ports: Vec<String> = self
             .liveu
             .get_unit_custom_names(&self.boss_id, self.config.custom_port_names.clone())
             .await
             .unwrap()
-        {
-            current_modems.push(interface.port);
-        }
+            .into_iter()
+            .map(|interface| interface.port)
+            .collect();
+        tracker.reset(&ports);
 
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(
@@ -71,7 +77,6 @@ impl Monitor {
             }
 
             let mut current = Vec::new();
-            let mut new_modems = Vec::new();
             {
                 let interfaces = self
                     .liveu
@@ -93,81 +98,70 @@ impl Monitor {
                         technology: interface.technology,
                         is_currently_roaming: interface.is_currently_roaming,
                     });
-                    // we got a new interface
-                    if !current_modems.contains(&interface.port) {
-                        // println!("New modem {}", interface.port);
-                        new_modems.push(interface.port.to_owned());
-                        current_modems.push(interface.port.to_owned());
-                    }
 
                     current.push(interface.port);
                 }
             }
 
-            if self.config.liveu.monitor.modems{
-                // check diff between current and prev
-                let mut removed_modems = Vec::new();
-                for modem in current_modems.iter() {
-                    if !current.contains(modem) {
-                        // println!("Removed modem {}", modem);
-                        removed_modems.push(modem.to_owned());
-                    }
-                }
+            let modems = (self.modem_sync.lock().await).clone();
 
-                for rem in removed_modems.iter() {
-                    let index = current_modems.iter().position(|m| m == rem).unwrap();
-                    current_modems.swap_remove(index);
-                }
+            // don't announce the changes that happened while we were not streaming
+            if ignore {
+                tracker.reset(&current);
+                links.reset(&modems);
+                ignore = false;
+                continue;
+            }
 
-                let message = Self::generate_modems_message(new_modems, removed_modems, self.lang.clone());
+            let changes = tracker.update(&current, Instant::now());
+            let link_changes = links.update(&modems);
 
-                if !ignore && !message.is_empty() {
-                    let _ = self
-                        .client
-                        .say(
-                            self.config.twitch.channel.to_owned(),
-                            "LiveU: ".to_string() + &message,
-                        )
-                        .await;
-                }
+            if self.config.liveu.monitor.modems && !changes.is_empty() {
+                self.publish(Event::ModemsChanged {
+                    added: changes.new_modems,
+                    removed: changes.removed_modems,
+                    flapped: changes.flapped_modems,
+                    modems,
+                });
             }
 
-            if ignore {
-                ignore = false;
+            for event in link_changes {
+                let enabled = match event {
+                    Event::TechnologyChanged { .. } => self.config.liveu.monitor.modems_technology,
+                    _ => self.config.liveu.monitor.modems_roaming,
+                };
+
+                if enabled {
+                    self.publish(event);
+                }
             }
         }
     }
 
-    fn generate_modems_message(new_modems: Vec<String>, removed_modems: Vec<String>, lang: String) -> String {
-        let mut message = String::new();
+    pub async fn monitor_stream(&self) {
+        let mut tracker = StreamTracker::default();
+        tracker.update(self.get_stream_state().await);
 
-        if !new_modems.is_empty() {
-            let a = if new_modems.len() > 1 { t!("monitor.are", locale = &lang) } else { t!("monitor.is", locale = &lang) };
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                self.config.liveu.monitor.stream_interval,
+            ))
+            .await;
 
-            message += t!(
-                "monitor.new_modem", 
-                locale = &lang, 
-                newModems = &new_modems.join(", "), 
-                isORare = &a
-            ).as_str();
+            if let Some(event) = tracker.update(self.get_stream_state().await) {
+                self.publish(event);
+            }
         }
+    }
 
-        if !removed_modems.is_empty() {
-            let a = if removed_modems.len() > 1 {
-                t!("monitor.have", locale = &lang)
-            } else {
-                t!("monitor.has", locale = &lang)
-            };
-
-            message += t!(
-                "monitor.remove_modem", 
-                locale = &lang, 
-                removedModems = &removed_modems.join(", "), 
-                haveORhas = &a
-            ).as_str();
+    /// None when LiveU couldn't be reached, which says nothing about the unit
+    async fn get_stream_state(&self) -> Option<StreamState> {
+        match self.liveu.get_video(&self.boss_id).await {
+            Ok(video) if video.bitrate.is_some() => Some(StreamState::Streaming),
+            Ok(_) => Some(StreamState::Idle),
+            Err(Error::StatusNotAvailable) => Some(StreamState::Offline),
+            Err(_) => None,
         }
-
-        message
     }
 
     pub async fn monitor_battery(&self) {
@@ -178,6 +172,7 @@ impl Monitor {
             discharging: false,
             charging: false,
         };
+        let mut fired_time_thresholds = Vec::new();
 
         loop {
             tokio::time::sleep(tokio::time::Duration::from_secs(
@@ -221,38 +216,24 @@ impl Monitor {
 
             if self.config.liveu.monitor.battery{
                 if self.config.liveu.monitor.battery_charging {
-                    self.battery_charging(&battery, &prev).await;
+                    self.battery_charging(&battery, &prev);
                 }
     
-                for percentage in &self.config.liveu.monitor.battery_notification {
-                    self.battery_percentage_message(*percentage, &battery, &prev)
-                        .await;
-                }
+                self.battery_percentage_message(&battery, &prev);
+                self.battery_time_message(&battery, &mut fired_time_thresholds);
             }
 
             prev = battery;
         }
     }
 
-    pub async fn battery_charging(&self, battery: &liveu::Battery, prev: &liveu::Battery) {
+    pub fn battery_charging(&self, battery: &liveu::Battery, prev: &liveu::Battery) {
         if !battery.charging && battery.discharging && !prev.discharging {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    t!("monitor.rip_power", locale = &self.lang),
-                )
-                .await;
+            self.publish(Event::ChargingChanged { charging: false });
         }
 
         if battery.charging && !battery.discharging && !prev.charging {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    t!("monitor.now_charging", locale = &self.lang),
-                )
-                .await;
+            self.publish(Event::ChargingChanged { charging: true });
         }
 
         if battery.percentage < 100
@@ -260,13 +241,7 @@ impl Monitor {
             && !battery.discharging
             && (prev.charging || prev.discharging)
         {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    t!("monitor.too_hot", locale = &self.lang),
-                )
-                .await;
+            self.publish(Event::TooHot);
         }
 
         if battery.percentage == 100
@@ -275,36 +250,530 @@ impl Monitor {
             && prev.charging
             && !prev.discharging
         {
-            let _ = self
-                .client
-                .say(
-                    self.config.twitch.channel.to_owned(),
-                    t!("monitor.fully_charged", locale = &self.lang),
-                )
-                .await;
+            self.publish(Event::FullyCharged);
+        }
+    }
+
+    pub fn battery_percentage_message(&self, current: &liveu::Battery, prev: &liveu::Battery) {
+        let crossed = crossed_threshold(
+            &self.config.liveu.monitor.battery_notification,
+            prev.percentage,
+            current.percentage,
+        );
+
+        if crossed.is_some() {
+            self.publish(Event::BatteryThreshold {
+                percentage: current.percentage,
+                charging: current.charging,
+            });
+        }
+    }
+
+    pub fn battery_time_message(&self, current: &liveu::Battery, fired: &mut Vec<u32>) {
+        let crossed = crossed_time_thresholds(
+            &self.config.liveu.monitor.battery_time_notification,
+            fired,
+            current,
+        );
+
+        if crossed {
+            self.publish(Event::BatteryTimeThreshold {
+                minutes: current.run_time_to_empty,
+            });
+        }
+    }
+
+    /// Sends the event to everyone listening. Nobody listening is fine.
+    fn publish(&self, event: Event) {
+        let _ = self.events.send(event);
+    }
+}
+
+/// Returns the lowest threshold that was crossed going down from `prev` to `current`
+fn crossed_threshold<T: Ord + Copy>(thresholds: &[T], prev: T, current: T) -> Option<T> {
+    thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| prev > *threshold && current <= *threshold)
+        .min()
+}
+
+/// Minutes the estimate has to go back above a threshold before it can fire again,
+/// the estimate of the unit jumps around a bit
+const BATTERY_TIME_MARGIN: u32 = 5;
+
+/// Returns true when the estimate went below a threshold that didn't fire yet.
+/// `fired` keeps the thresholds that fired, they are armed again after charging
+/// or when the estimate goes back above them by `BATTERY_TIME_MARGIN`.
+fn crossed_time_thresholds(thresholds: &[u32], fired: &mut Vec<u32>, battery: &liveu::Battery) -> bool {
+    if !battery.discharging {
+        fired.clear();
+        return false;
+    }
+
+    // a run time of 0 means the unit has no estimate
+    if battery.run_time_to_empty == 0 {
+        return false;
+    }
+
+    let minutes = battery.run_time_to_empty;
+    fired.retain(|threshold| minutes < threshold.saturating_add(BATTERY_TIME_MARGIN));
+
+    let crossed: Vec<u32> = thresholds
+        .iter()
+        .copied()
+        .filter(|threshold| minutes <= *threshold && !fired.contains(threshold))
+        .collect();
+
+    fired.extend(&crossed);
+
+    !crossed.is_empty()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamState {
+    Offline,
+    Idle,
+    Streaming,
+}
+
+/// Polls in a row the unit has to be offline before it's reported
+const OFFLINE_POLLS: u32 = 2;
+
+/// Turns the polled stream states into events
+#[derive(Debug, Default)]
+pub struct StreamTracker {
+    state: Option<StreamState>,
+    offline_polls: u32,
+}
+
+impl StreamTracker {
+    pub fn update(&mut self, state: Option<StreamState>) -> Option<Event> {
+        // keep the last known state when the request itself failed
+        let state = state?;
+
+        if state == StreamState::Offline {
+            self.offline_polls += 1;
+
+            if self.offline_polls < OFFLINE_POLLS {
+                return None;
+            }
+        } else {
+            self.offline_polls = 0;
+        }
+
+        // nothing to compare with on the first poll
+        let prev = self.state.replace(state)?;
+
+        if state == prev {
+            return None;
+        }
+
+        let event = match (prev, state) {
+            (_, StreamState::Offline) => Event::UnitOffline,
+            (StreamState::Offline, StreamState::Idle) => Event::UnitOnline,
+            (_, StreamState::Streaming) => Event::StreamStarted,
+            (StreamState::Streaming, StreamState::Idle) => Event::StreamStopped,
+            (StreamState::Idle, StreamState::Idle) => unreachable!(),
+        };
+
+        Some(event)
+    }
+}
+
+/// Modems that changed since the previous poll
+#[derive(Debug, Default)]
+pub struct ModemChanges {
+    pub new_modems: Vec<String>,
+    pub removed_modems: Vec<String>,
+    pub flapped_modems: Vec<ModemFlap>,
+}
+
+impl ModemChanges {
+    pub fn is_empty(&self) -> bool {
+        self.new_modems.is_empty() && self.removed_modems.is_empty() && self.flapped_modems.is_empty()
+    }
+}
+
+/// A modem that disconnected and came back within the grace period
+#[derive(Debug, Clone, Serialize)]
+pub struct ModemFlap {
+    pub port: String,
+    pub count: u32,
+    #[serde(rename = "window_secs", serialize_with = "serialize_secs")]
+    pub window: Duration,
+}
+
+fn serialize_secs<S: serde::Serializer>(duration: &Duration, serializer: S) -> Result<S::Ok, S::Error> {
+    serializer.serialize_u64(duration.as_secs())
+}
+
+#[derive(Debug)]
+struct TrackedModem {
+    port: String,
+    missing_since: Option<Instant>,
+    flaps: u32,
+    flapping_since: Option<Instant>,
+}
+
+/// A modem has to come back at least this often within the flap window to be reported,
+/// a single short blip is exactly what the grace period hides
+const MIN_FLAPS: u32 = 2;
+
+/// Debounces modem connects and disconnects.
+///
+/// A modem that disappears is only reported as removed once it has been gone
+/// for the whole grace period. If it comes back before that it counts as a flap,
+/// and all flaps are reported together once the flap window has passed.
+#[derive(Debug)]
+pub struct ModemTracker {
+    grace_period: Duration,
+    flap_window: Duration,
+    modems: Vec<TrackedModem>,
+}
+
+impl ModemTracker {
+    pub fn new(grace_period: Duration, flap_window: Duration) -> Self {
+        Self {
+            grace_period,
+            flap_window,
+            modems: Vec::new(),
+        }
+    }
+
+    /// Forgets all pending changes and treats the given ports as connected
+    pub fn reset(&mut self, ports: &[String]) {
+        self.modems = ports
+            .iter()
+            .map(|port| TrackedModem {
+                port: port.to_owned(),
+                missing_since: None,
+                flaps: 0,
+                flapping_since: None,
+            })
+            .collect();
+    }
+
+    pub fn update(&mut self, ports: &[String], now: Instant) -> ModemChanges {
+        let mut changes = ModemChanges::default();
+
+        for port in ports {
+            match self.modems.iter_mut().find(|m| &m.port == port) {
+                Some(modem) => {
+                    // came back before the grace period ran out
+                    if modem.missing_since.take().is_some() {
+                        modem.flaps += 1;
+                        modem.flapping_since.get_or_insert(now);
+                    }
+                }
+                None => {
+                    changes.new_modems.push(port.to_owned());
+                    self.modems.push(TrackedModem {
+                        port: port.to_owned(),
+                        missing_since: None,
+                        flaps: 0,
+                        flapping_since: None,
+                    });
+                }
+            }
         }
+
+        let grace_period = self.grace_period;
+        self.modems.retain_mut(|modem| {
+            if ports.contains(&modem.port) {
+                return true;
+            }
+
+            let missing_since = *modem.missing_since.get_or_insert(now);
+
+            if now.duration_since(missing_since) >= grace_period {
+                if let Some(since) = modem.flapping_since.filter(|_| modem.flaps >= MIN_FLAPS) {
+                    changes.flapped_modems.push(ModemFlap {
+                        port: modem.port.to_owned(),
+                        count: modem.flaps,
+                        window: now.duration_since(since),
+                    });
+                }
+
+                changes.removed_modems.push(modem.port.to_owned());
+                return false;
+            }
+
+            true
+        });
+
+        for modem in self.modems.iter_mut() {
+            let since = match modem.flapping_since {
+                Some(since) => since,
+                None => continue,
+            };
+
+            let window = now.duration_since(since);
+
+            if window >= self.flap_window {
+                if modem.flaps >= MIN_FLAPS {
+                    changes.flapped_modems.push(ModemFlap {
+                        port: modem.port.to_owned(),
+                        count: modem.flaps,
+                        window,
+                    });
+                }
+
+                modem.flaps = 0;
+                modem.flapping_since = None;
+            }
+        }
+
+        changes
     }
+}
+
+/// Remembers the technology and roaming state of every modem
+#[derive(Debug, Default)]
+pub struct LinkTracker {
+    links: HashMap<String, (String, bool)>,
+}
+
+impl LinkTracker {
+    /// Forgets the previous state and starts over from the given modems
+    pub fn reset(&mut self, modems: &[Modem]) {
+        self.links = modems
+            .iter()
+            .map(|m| (m.port.to_owned(), (m.technology.to_owned(), m.is_currently_roaming)))
+            .collect();
+    }
+
+    pub fn update(&mut self, modems: &[Modem]) -> Vec<Event> {
+        let mut changes = Vec::new();
+
+        for modem in modems {
+            let current = (modem.technology.to_owned(), modem.is_currently_roaming);
+
+            if let Some((technology, roaming)) = self.links.insert(modem.port.to_owned(), current) {
+                // an empty technology just means the unit didn't report one this time
+                if !technology.is_empty()
+                    && !modem.technology.is_empty()
+                    && technology != modem.technology
+                {
+                    changes.push(Event::TechnologyChanged {
+                        port: modem.port.to_owned(),
+                        from: technology,
+                        to: modem.technology.to_owned(),
+                    });
+                }
 
-    pub async fn battery_percentage_message(
-        &self,
-        percentage: u8,
-        current: &liveu::Battery,
-        prev: &liveu::Battery,
-    ) {
-        if current.percentage == percentage && prev.percentage > percentage {
-            let a = if current.charging { t!("monitor.charging", locale = &self.lang)} else { t!("monitor.not_charging", locale = &self.lang)};
-
-            let message = t!(
-                "monitor.battery_percentage", 
-                locale = &self.lang,
-                percent = &percentage.to_string(),
-                chargingORnot = &a
-            );
-
-            let _ = self
-                .client
-                .say(self.config.twitch.channel.to_owned(), message)
-                .await;
+                if roaming != modem.is_currently_roaming {
+                    changes.push(Event::RoamingChanged {
+                        port: modem.port.to_owned(),
+                        roaming: modem.is_currently_roaming,
+                    });
+                }
+            }
         }
+
+        changes
+    }
+}
+
+/// Ranks a cellular technology, higher is better. Unknown technologies rank lowest.
+pub fn technology_rank(technology: &str) -> u8 {
+    let technology = technology.to_uppercase();
+
+    if technology.contains("5G") || technology.contains("NR") {
+        5
+    } else if technology.contains("LTE") || technology.contains("4G") {
+        4
+    } else if technology.contains("3G")
+        || technology.contains("HSPA")
+        || technology.contains("HSDPA")
+        || technology.contains("HSUPA")
+        || technology.contains("UMTS")
+        || technology.contains("WCDMA")
+    {
+        3
+    } else if technology.contains("2G")
+        || technology.contains("EDGE")
+        || technology.contains("GPRS")
+        || technology.contains("GSM")
+    {
+        2
+    } else {
+        0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn discharging(minutes: u32) -> liveu::Battery {
+        liveu::Battery {
+            connected: false,
+            percentage: 50,
+            run_time_to_empty: minutes,
+            discharging: true,
+            charging: false,
+        }
+    }
+
+    #[test]
+    fn battery_time_fires_once_while_estimate_jitters() {
+        let thresholds = [30, 15];
+        let mut fired = Vec::new();
+        let mut crossed = |minutes| crossed_time_thresholds(&thresholds, &mut fired, &discharging(minutes));
+
+        assert!(!crossed(40));
+        assert!(crossed(29));
+        assert!(crossed(14));
+        assert!(!crossed(16));
+        assert!(!crossed(14));
+        // went back above 15 by the margin, so it can fire again
+        assert!(!crossed(21));
+        assert!(crossed(15));
+    }
+
+    #[test]
+    fn battery_time_rearms_after_charging() {
+        let thresholds = [15];
+        let mut fired = Vec::new();
+
+        assert!(crossed_time_thresholds(&thresholds, &mut fired, &discharging(14)));
+        assert!(!crossed_time_thresholds(&thresholds, &mut fired, &discharging(14)));
+
+        let mut charging = discharging(14);
+        charging.discharging = false;
+        charging.charging = true;
+        assert!(!crossed_time_thresholds(&thresholds, &mut fired, &charging));
+
+        assert!(crossed_time_thresholds(&thresholds, &mut fired, &discharging(14)));
+    }
+
+    fn ports(ports: &[&str]) -> Vec<String> {
+        ports.iter().map(|p| p.to_string()).collect()
+    }
+
+    fn tracker() -> (ModemTracker, Instant) {
+        let mut tracker = ModemTracker::new(Duration::from_secs(30), Duration::from_secs(120));
+        tracker.reset(&ports(&["SIM1", "SIM2"]));
+
+        (tracker, Instant::now())
+    }
+
+    #[test]
+    fn removes_modem_after_grace_period() {
+        let (mut tracker, start) = tracker();
+
+        assert!(tracker.update(&ports(&["SIM1"]), start).is_empty());
+        assert!(tracker.update(&ports(&["SIM1"]), start + Duration::from_secs(29)).is_empty());
+
+        let changes = tracker.update(&ports(&["SIM1"]), start + Duration::from_secs(30));
+        assert_eq!(changes.removed_modems, ports(&["SIM2"]));
+        assert!(changes.flapped_modems.is_empty());
+
+        let changes = tracker.update(&ports(&["SIM1", "SIM2"]), start + Duration::from_secs(40));
+        assert_eq!(changes.new_modems, ports(&["SIM2"]));
+    }
+
+    #[test]
+    fn removes_modem_right_away_without_grace_period() {
+        let mut tracker = ModemTracker::new(Duration::ZERO, Duration::from_secs(120));
+        tracker.reset(&ports(&["SIM1", "SIM2"]));
+
+        let changes = tracker.update(&ports(&["SIM1"]), Instant::now());
+        assert_eq!(changes.removed_modems, ports(&["SIM2"]));
+    }
+
+    #[test]
+    fn batches_flaps_until_window_passed() {
+        let (mut tracker, start) = tracker();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(tracker.update(&ports(&["SIM1"]), at(0)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(5)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1"]), at(10)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(15)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(120)).is_empty());
+
+        let changes = tracker.update(&ports(&["SIM1", "SIM2"]), at(125));
+        assert_eq!(changes.flapped_modems.len(), 1);
+        assert_eq!(changes.flapped_modems[0].port, "SIM2");
+        assert_eq!(changes.flapped_modems[0].count, 2);
+        assert_eq!(changes.flapped_modems[0].window, Duration::from_secs(120));
+        assert!(changes.new_modems.is_empty() && changes.removed_modems.is_empty());
+
+        // the count starts over after reporting
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(300)).is_empty());
+    }
+
+    #[test]
+    fn ignores_single_blip() {
+        let (mut tracker, start) = tracker();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        assert!(tracker.update(&ports(&["SIM1"]), at(0)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(5)).is_empty());
+        assert!(tracker.update(&ports(&["SIM1", "SIM2"]), at(200)).is_empty());
+    }
+
+    #[test]
+    fn reports_flaps_of_removed_modem() {
+        let (mut tracker, start) = tracker();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        tracker.update(&ports(&["SIM1"]), at(0));
+        tracker.update(&ports(&["SIM1", "SIM2"]), at(5));
+        tracker.update(&ports(&["SIM1"]), at(10));
+        tracker.update(&ports(&["SIM1", "SIM2"]), at(15));
+        tracker.update(&ports(&["SIM1"]), at(20));
+
+        let changes = tracker.update(&ports(&["SIM1"]), at(50));
+        assert_eq!(changes.removed_modems, ports(&["SIM2"]));
+        assert_eq!(changes.flapped_modems.len(), 1);
+        assert_eq!(changes.flapped_modems[0].count, 2);
+    }
+
+    #[test]
+    fn reset_forgets_pending_changes() {
+        let (mut tracker, start) = tracker();
+        let at = |secs| start + Duration::from_secs(secs);
+
+        tracker.update(&ports(&["SIM1"]), at(0));
+        tracker.update(&ports(&["SIM1", "SIM2"]), at(5));
+        tracker.update(&ports(&["SIM1"]), at(10));
+        tracker.update(&ports(&["SIM1", "SIM2"]), at(15));
+
+        tracker.reset(&ports(&["SIM1"]));
+
+        assert!(tracker.update(&ports(&["SIM1"]), at(200)).is_empty());
+
+        let changes = tracker.update(&ports(&["SIM1", "SIM2"]), at(210));
+        assert_eq!(changes.new_modems, ports(&["SIM2"]));
+        assert!(changes.flapped_modems.is_empty());
+    }
+
+    #[test]
+    fn single_offline_poll_is_ignored() {
+        let mut tracker = StreamTracker::default();
+
+        assert!(tracker.update(Some(StreamState::Streaming)).is_none());
+        assert!(tracker.update(Some(StreamState::Offline)).is_none());
+        assert!(tracker.update(Some(StreamState::Streaming)).is_none());
+
+        assert!(tracker.update(Some(StreamState::Offline)).is_none());
+        assert!(matches!(tracker.update(Some(StreamState::Offline)), Some(Event::UnitOffline)));
+        assert!(tracker.update(Some(StreamState::Offline)).is_none());
+        assert!(matches!(tracker.update(Some(StreamState::Idle)), Some(Event::UnitOnline)));
+    }
+
+    #[test]
+    fn failed_requests_keep_the_last_state() {
+        let mut tracker = StreamTracker::default();
+
+        assert!(tracker.update(None).is_none());
+        assert!(tracker.update(Some(StreamState::Idle)).is_none());
+        assert!(tracker.update(None).is_none());
+        assert!(tracker.update(None).is_none());
+        assert!(matches!(tracker.update(Some(StreamState::Streaming)), Some(Event::StreamStarted)));
+        assert!(matches!(tracker.update(Some(StreamState::Idle)), Some(Event::StreamStopped)));
     }
 }
@@ -1,8 +1,8 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
 use anyhow::{Context, Result};
-use liveu_stats_bot::{config::Config, liveu::{Liveu, Battery}, liveu_monitor::{Monitor, Modem}, twitch::Twitch, srt};
+use liveu_stats_bot::{chat::{self, Chat, LogSink}, commands::CommandHandler, config::Config, discord, kick::Kick, liveu::{Liveu, Battery}, liveu_monitor::{Monitor, Modem}, mqtt::Mqtt, telegram::Telegram, twitch::{ConnectionState, Twitch}, srt, webhook, youtube::Youtube};
 use actix_cors::Cors;
 use actix_web::{web, App, HttpServer};
 use serde::Serialize;
@@ -44,28 +44,97 @@ async fn main() -> Result<()> {
                 charging: false,
             }));
         let total_bitrate_sync: Arc<Mutex<u32>> = Arc::new(Mutex::new(0));
-        let srt_bitrate_sync: Arc<Mutex<i64>> = Arc::new(Mutex::new(0));
-        let srt_bitrate = Arc::clone(&srt_bitrate_sync);
+        let srt_sync: Arc<Mutex<srt::SrtStatus>> = Arc::new(Mutex::new(srt::SrtStatus::default()));
+        let srt_stats = Arc::clone(&srt_sync);
+        let (events, _) = broadcast::channel(64);
 
         if let Some(srt) = config.srt.clone() {
-            tokio::spawn(async move { srt::srt_bitrate_monitor(&srt, srt_bitrate.clone()).await });
+            if let Some(alerts) = srt.alerts.clone() {
+                println!("Srt: monitoring packet loss and rtt");
+                let srt_stats = Arc::clone(&srt_sync);
+                let events = events.clone();
+                tokio::spawn(async move { srt::srt_alerts_monitor(alerts, srt_stats, events).await });
+            }
+
+            tokio::spawn(async move { srt::srt_stats_monitor(&srt, srt_stats.clone()).await });
+        }
+
+        if let Some(discord) = config.discord.clone() {
+            println!("Discord: sending notifications to webhook");
+            let lang = config.lang.clone();
+            let receiver = events.subscribe();
+            tokio::spawn(async move { discord::run(discord, lang, receiver).await });
+        }
+
+        for webhook in config.webhooks.clone().unwrap_or_default() {
+            println!("Webhook: sending notifications to {}", webhook.url);
+            let boss_id = liveu_boss_id.to_owned();
+            let lang = config.lang.clone();
+            let receiver = events.subscribe();
+            tokio::spawn(async move { webhook::run(webhook, boss_id, lang, receiver).await });
+        }
+
+        let handler = CommandHandler {
+            liveu: liveu.clone(),
+            boss_id: liveu_boss_id.to_owned(),
+            monitored_boss_id: liveu_boss_id.to_owned(),
+            config: config.clone(),
+            lang: config.lang.clone(),
+            modem_sync: Arc::clone(&modem_sync),
+            battery_sync: Arc::clone(&battery_sync),
+            srt_sync: Arc::clone(&srt_sync),
+            events: events.clone(),
+        };
+
+        if let Some(telegram) = config.telegram.clone() {
+            println!("Telegram: listening for commands");
+            Telegram::run(telegram, handler.clone());
+        }
+
+        if let Some(mqtt) = config.mqtt.clone() {
+            println!("MQTT: Connecting to {}...", mqtt.host);
+            Mqtt::run(mqtt, handler.clone());
+        }
+
+        if let Some(youtube) = config.youtube.clone() {
+            println!("YouTube: waiting for a live chat");
+            Chat::run(Arc::new(Youtube::new(youtube)), handler.clone());
+        }
+
+        if let Some(kick) = config.kick.clone() {
+            println!("Kick: Connecting...");
+            Chat::run(Arc::new(Kick::new(kick)), handler.clone());
+        }
+
+        let mut twitch_join_handle = None;
+        let mut twitch_state = None;
+
+        if let Some(twitch) = config.twitch.clone() {
+            println!("\nTwitch: Connecting...");
+            let twitch = Arc::new(Twitch::new(twitch));
+            twitch_state = Some(twitch.state());
+            twitch_join_handle = Some(Chat::run(twitch, handler.clone()));
         }
 
-        println!("\nTwitch: Connecting...");
-        let (twitch_client, twitch_join_handle) =
-            Twitch::run(
-                config.clone(), 
-                liveu.clone(), 
-                liveu_boss_id.to_owned(), 
-                Arc::clone(&modem_sync), 
-                Arc::clone(&battery_sync), 
-                Arc::clone(&srt_bitrate_sync),
-            );
-        println!("Twitch: Connected");
+        // only returns when twitch can't log in anymore
+        let twitch = async move {
+            match twitch_join_handle {
+                Some(twitch_join_handle) => Ok(twitch_join_handle.await??),
+                // nothing else keeps the app running when there's no twitch chat
+                None => std::future::pending::<Result<()>>().await,
+            }
+        };
+
+        // without a chat the notifications would go nowhere, so print them instead
+        if config.twitch.is_none() && config.youtube.is_none() && config.kick.is_none() {
+            println!("No chat configured, printing the notifications");
+            tokio::spawn(chat::announce(Arc::new(LogSink), config.lang.clone(), events.subscribe()));
+        }
+
+        let mut server = None;
 
         {
             let monitor = Monitor {
-                client: twitch_client.clone(),
                 config: config.clone(),
                 liveu: liveu.clone(),
                 boss_id: liveu_boss_id.to_owned(),
@@ -73,17 +142,22 @@ async fn main() -> Result<()> {
                 total_bitrate: Arc::clone(&total_bitrate_sync),
                 modem_sync: Arc::clone(&modem_sync),
                 battery_sync: Arc::clone(&battery_sync),
+                events: events.clone(),
             };
 
-            if config.liveu.monitor.modems || config.server {
-                if config.liveu.monitor.modems{
+            let modem_alerts = config.liveu.monitor.modems
+                || config.liveu.monitor.modems_technology
+                || config.liveu.monitor.modems_roaming;
+
+            if modem_alerts || config.polls_stats() {
+                if modem_alerts {
                     println!("Liveu: monitoring modems");
                 }
                 let modems = monitor.clone();
                 tokio::spawn(async move { modems.monitor_modems().await });
             }
 
-            if config.liveu.monitor.battery || config.server {
+            if config.liveu.monitor.battery || config.polls_stats() {
                 if config.liveu.monitor.battery{
                     println!("Liveu: monitoring battery");
                 }
@@ -91,15 +165,23 @@ async fn main() -> Result<()> {
                 tokio::spawn(async move { battery.monitor_battery().await });
             }
 
+            if config.liveu.monitor.stream {
+                println!("Liveu: monitoring stream");
+                let stream = monitor.clone();
+                tokio::spawn(async move { stream.monitor_stream().await });
+            }
+
             if config.server {
                 let port = 8183;        
                 let data = AppState{
                     monitor: monitor.clone(),
-                    srt_bitrate: Arc::clone(&srt_bitrate_sync),
+                    srt: Arc::clone(&srt_sync),
+                    srt_enabled: config.srt.is_some(),
+                    twitch: twitch_state,
                 };
 
                 println!("Server is starting on port: {}", port);       
-                HttpServer::new(move || {
+                server = Some(HttpServer::new(move || {
                     let cors = Cors::default()
                         .allow_any_origin()
                         .allowed_methods(vec!["GET"]);
@@ -107,34 +189,64 @@ async fn main() -> Result<()> {
                     App::new()
                         .app_data(web::Data::new(data.clone()))
                         .route("/stats", web::to(do_get))
+                        .route("/health", web::to(health))
                         .wrap(cors)
                 })
                 .bind(("127.0.0.1", port))?
-                .run()
-                .await?;
+                .run());
             }
         }
-        twitch_join_handle.await?;
+
+        match server {
+            Some(server) => tokio::select! {
+                res = server => res?,
+                res = twitch => res?,
+            },
+            None => twitch.await?,
+        }
     }
 
     Ok(())
 }
 
 async fn do_get(data: web::Data<AppState>) -> web::Json<JsonData> {
+    let srt = match data.srt_enabled {
+        true => Some(data.srt.lock().await.clone()),
+        false => None,
+    };
+
     let obj = web::Json(JsonData{
         modems: (data.monitor.modem_sync.lock().await).clone(),
         total_bitrate: *data.monitor.total_bitrate.lock().await,
-        srt_bitrate: *data.srt_bitrate.lock().await,
+        srt_bitrate: srt.as_ref().map(|s| s.bitrate()).unwrap_or_default(),
+        srt,
         battery: (data.monitor.battery_sync.lock().await).clone(),
     });  
 
     obj
 }
 
+async fn health(data: web::Data<AppState>) -> web::Json<Health> {
+    let twitch = match &data.twitch {
+        Some(state) => Some(state.lock().await.clone()),
+        None => None,
+    };
+
+    web::Json(Health { twitch })
+}
+
 #[derive(Clone)]
 struct AppState {
     monitor: Monitor,
-    srt_bitrate: Arc<Mutex<i64>>,
+    srt: Arc<Mutex<srt::SrtStatus>>,
+    srt_enabled: bool,
+    twitch: Option<Arc<Mutex<ConnectionState>>>,
+}
+
+#[derive(Serialize)]
+struct Health {
+    /// Not set when twitch isn't configured
+    twitch: Option<ConnectionState>,
 }
 
 #[derive(Serialize)]
@@ -143,4 +255,6 @@ struct JsonData {
     total_bitrate: u32,
     battery: Battery,
     srt_bitrate: i64,
+    /// Not set when srt isn't configured
+    srt: Option<srt::SrtStatus>,
 }
\ No newline at end of file
@@ -0,0 +1,214 @@
+use std::{sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use rumqttc::{AsyncClient, Event, MqttOptions, Packet, QoS};
+
+use crate::{
+    commands::{Args, Command, CommandHandler, Reply},
+    config, nginx,
+};
+
+const DEFAULT_PORT: u16 = 1883;
+const DEFAULT_CLIENT_ID: &str = "liveu_stats_bot";
+const DEFAULT_TOPIC_PREFIX: &str = "liveu";
+
+#[derive(Clone)]
+pub struct Mqtt {
+    config: config::Mqtt,
+    handler: CommandHandler,
+    client: AsyncClient,
+    /// `<prefix>/<unit>`, every topic starts with this
+    base_topic: String,
+}
+
+/// Publishes the messages of a running command to the response topic
+struct MqttReply {
+    mqtt: Mqtt,
+}
+
+#[async_trait]
+impl Reply for MqttReply {
+    async fn reply(&self, message: String) {
+        self.mqtt.publish("command/response", message, false).await;
+    }
+}
+
+impl Mqtt {
+    pub fn run(config: config::Mqtt, handler: CommandHandler) -> tokio::task::JoinHandle<()> {
+        let mut options = MqttOptions::new(
+            config.client_id.as_deref().unwrap_or(DEFAULT_CLIENT_ID),
+            config.host.to_owned(),
+            config.port.unwrap_or(DEFAULT_PORT),
+        );
+        options.set_keep_alive(Duration::from_secs(30));
+
+        if let Some(username) = &config.username {
+            options.set_credentials(username, config.password.to_owned().unwrap_or_default());
+        }
+
+        let (client, mut eventloop) = AsyncClient::new(options, 32);
+
+        let base_topic = format!(
+            "{}/{}",
+            config.topic_prefix.as_deref().unwrap_or(DEFAULT_TOPIC_PREFIX),
+            sanitize(config.unit.as_deref().unwrap_or(&handler.boss_id))
+        );
+
+        let mqtt = Self {
+            config,
+            handler,
+            client,
+            base_topic,
+        };
+
+        let telemetry = mqtt.clone();
+        tokio::spawn(async move { telemetry.publish_telemetry().await });
+
+        tokio::spawn(async move {
+            loop {
+                match eventloop.poll().await {
+                    // subscriptions are gone after reconnecting, so subscribe every time
+                    Ok(Event::Incoming(Packet::ConnAck(_))) => {
+                        println!("MQTT: Connected");
+
+                        if mqtt.config.commands {
+                            let topic = format!("{}/command", mqtt.base_topic);
+                            let _ = mqtt.client.try_subscribe(topic, QoS::AtLeastOnce);
+                        }
+                    }
+                    Ok(Event::Incoming(Packet::Publish(publish))) => {
+                        let payload = String::from_utf8_lossy(&publish.payload).to_string();
+                        mqtt.handle_command(payload);
+                    }
+                    Ok(_) => {}
+                    Err(e) => {
+                        println!("MQTT: Connection error: {}", e);
+                        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    }
+                }
+            }
+        })
+    }
+
+    /// Publishes the unit stats every time the monitor polls the unit
+    async fn publish_telemetry(&self) {
+        let mut ports: Vec<String> = Vec::new();
+
+        loop {
+            tokio::time::sleep(tokio::time::Duration::from_secs(
+                self.handler.config.liveu.monitor.modems_interval,
+            ))
+            .await;
+
+            let modems = (self.handler.modem_sync.lock().await).clone();
+            let battery = (self.handler.battery_sync.lock().await).clone();
+            let mut total_bitrate = 0;
+
+            for modem in &modems {
+                let topic = format!("modems/{}", sanitize(&modem.port));
+                total_bitrate += modem.uplink_kbps;
+
+                self.publish(&format!("{}/uplink_kbps", topic), modem.uplink_kbps.to_string(), true).await;
+                self.publish(&format!("{}/connected", topic), modem.connected.to_string(), true).await;
+                self.publish(&format!("{}/enabled", topic), modem.enabled.to_string(), true).await;
+                self.publish(&format!("{}/technology", topic), modem.technology.to_owned(), true).await;
+                self.publish(&format!("{}/roaming", topic), modem.is_currently_roaming.to_string(), true).await;
+            }
+
+            // the values are retained, so clear the modems that are gone
+            for port in ports.iter().filter(|p| !modems.iter().any(|m| &&m.port == p)) {
+                let topic = format!("modems/{}", sanitize(port));
+
+                self.publish(&format!("{}/uplink_kbps", topic), "0".to_string(), true).await;
+                self.publish(&format!("{}/connected", topic), "false".to_string(), true).await;
+            }
+
+            ports = modems.into_iter().map(|m| m.port).collect();
+
+            self.publish("total_bitrate", total_bitrate.to_string(), true).await;
+
+            // 255 means we don't know
+            if battery.percentage != 255 {
+                self.publish("battery/percentage", battery.percentage.to_string(), true).await;
+                self.publish("battery/charging", battery.charging.to_string(), true).await;
+                self.publish("battery/discharging", battery.discharging.to_string(), true).await;
+                self.publish("battery/connected", battery.connected.to_string(), true).await;
+                self.publish("battery/run_time_to_empty", battery.run_time_to_empty.to_string(), true).await;
+            }
+
+            if self.handler.config.srt.is_some() {
+                let srt = self.handler.srt_sync.lock().await.clone();
+                self.publish("srt/status", srt.name().to_string(), true).await;
+                self.publish("srt/bitrate", srt.bitrate().to_string(), true).await;
+
+                if let Some(stat) = srt.stat() {
+                    self.publish("srt/rtt", stat.rtt.to_string(), true).await;
+                    self.publish("srt/pkt_rcv_loss", stat.pkt_rcv_loss.to_string(), true).await;
+                    self.publish("srt/pkt_rcv_drop", stat.pkt_rcv_drop.to_string(), true).await;
+                    self.publish("srt/mbps_bandwidth", stat.mbps_bandwidth.to_string(), true).await;
+                    self.publish("srt/ms_rcv_buf", stat.ms_rcv_buf.to_string(), true).await;
+                    self.publish("srt/uptime", stat.uptime.to_string(), true).await;
+                }
+            }
+
+            if let Some(rtmp) = &self.handler.config.rtmp {
+                let bitrate = match nginx::get_rtmp_bitrate(rtmp).await {
+                    Ok(Some(bitrate)) => bitrate,
+                    _ => 0,
+                };
+                self.publish("rtmp/bitrate", bitrate.to_string(), true).await;
+            }
+        }
+    }
+
+    /// Runs a command published to `<prefix>/<unit>/command`.
+    /// The payload is the command name e.g. `start`, or one of the chat commands e.g. `!lustart`,
+    /// followed by the arguments e.g. `delay 3000`.
+    fn handle_command(&self, payload: String) {
+        let payload = payload.trim().to_owned();
+        let name = payload.split_ascii_whitespace().next().unwrap_or("");
+        let args = Args::parse(payload.split_ascii_whitespace().skip(1));
+
+        let command = match self.handler.get_command_by_name(name) {
+            Command::Unknown => self.handler.get_command(name),
+            command => command,
+        };
+
+        if command == Command::Unknown {
+            return;
+        }
+
+        let mqtt = self.clone();
+
+        // some commands take minutes, so don't hold up the connection
+        tokio::spawn(async move {
+            let res = if !command.controls_unit() {
+                mqtt.handler.handle_non_permission_commands(command, &args).await
+            } else {
+                mqtt.handler.audit(&payload, "mqtt", &mqtt.base_topic);
+
+                let reply = Arc::new(MqttReply { mqtt: mqtt.clone() });
+                mqtt.handler.handle_permission_commands(command, &args, reply).await
+            };
+
+            if let Ok(res) = res {
+                if !res.is_empty() {
+                    mqtt.publish("command/response", res, false).await;
+                }
+            }
+        });
+    }
+
+    async fn publish(&self, topic: &str, payload: String, retain: bool) {
+        let topic = format!("{}/{}", self.base_topic, topic);
+        let _ = self
+            .client
+            .publish(topic, QoS::AtMostOnce, retain, payload)
+            .await;
+    }
+}
+
+/// Removes the characters that have a special meaning in topics
+fn sanitize(name: &str) -> String {
+    name.replace(['/', '+', '#'], "_")
+}
@@ -1,13 +1,13 @@
 use std::sync::Arc;
-use tokio::sync::Mutex;
+use tokio::sync::{broadcast, Mutex};
 
-use serde::Deserialize;
+use serde::{Deserialize, Serialize};
 
-use crate::{config, error::Error};
+use crate::{config, error::Error, events::Event};
 
 use serde_json::Value;
 
-#[derive(Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct Stat {
     pub bitrate: i64,
@@ -22,7 +22,83 @@ pub struct Stat {
     pub uptime: i64,
 }
 
-pub async fn get_srt_bitrate(config: &config::Srt) -> Result<i64, Error> {
+/// What the srt monitor last saw
+#[derive(Serialize, Debug, Clone, Default)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum SrtStatus {
+    /// The stats weren't checked yet
+    #[default]
+    Pending,
+    /// The stats page of the server can't be reached
+    Unreachable { reason: String },
+    /// The server answered but the stats can't be read, e.g. the wrong `kind`
+    InvalidStats { reason: String },
+    /// The server is up but the publisher isn't connected
+    NotPublishing,
+    Live(Stat),
+}
+
+impl SrtStatus {
+    /// The stats of the publisher, only while it's live
+    pub fn stat(&self) -> Option<&Stat> {
+        match self {
+            SrtStatus::Live(stat) => Some(stat),
+            _ => None,
+        }
+    }
+
+    pub fn bitrate(&self) -> i64 {
+        self.stat().map(|s| s.bitrate).unwrap_or_default()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            SrtStatus::Pending => "pending",
+            SrtStatus::Unreachable { .. } => "unreachable",
+            SrtStatus::InvalidStats { .. } => "invalid_stats",
+            SrtStatus::NotPublishing => "not_publishing",
+            SrtStatus::Live(_) => "live",
+        }
+    }
+}
+
+impl From<Result<Option<Stat>, Error>> for SrtStatus {
+    /// Only failing to get the stats page makes the server unreachable,
+    /// not being able to read a page the server did send is something else
+    fn from(res: Result<Option<Stat>, Error>) -> Self {
+        match res {
+            Ok(Some(stat)) => SrtStatus::Live(stat),
+            Ok(None) => SrtStatus::NotPublishing,
+            Err(e @ Error::Json(_)) => SrtStatus::InvalidStats { reason: e.to_string() },
+            Err(e) => SrtStatus::Unreachable { reason: e.to_string() },
+        }
+    }
+}
+
+#[derive(Deserialize, Debug)]
+struct MediamtxList {
+    items: Vec<MediamtxConn>,
+}
+
+/// A connection in `/v3/srtconns/list` of MediaMTX
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MediamtxConn {
+    state: String,
+    path: String,
+    #[serde(rename = "msRTT")]
+    ms_rtt: f64,
+    packets_received_loss: u64,
+    packets_received_drop: u64,
+    bytes_received_loss: u64,
+    bytes_received_drop: u64,
+    mbps_link_capacity: f64,
+    mbps_receive_rate: f64,
+    ms_receive_buf: i32,
+}
+
+/// Gets the stats of the publisher, nothing when it isn't connected to the server
+pub async fn get_srt_stats(config: &config::Srt) -> Result<Option<Stat>, Error> {
     let res = reqwest::get(&config.url).await?;
 
     if res.status() != reqwest::StatusCode::OK {
@@ -32,23 +108,228 @@ pub async fn get_srt_bitrate(config: &config::Srt) -> Result<i64, Error> {
     let text = res.text().await?;
     let data: Value = serde_json::from_str(&text)?;
 
-    let publisher = &data["publishers"][&config.publisher];
+    parse_stats(config.kind, &config.publisher, data)
+}
+
+/// Gets the stats of the publisher from the stats page of the server
+pub fn parse_stats(kind: config::SrtKind, publisher: &str, data: Value) -> Result<Option<Stat>, Error> {
+    match kind {
+        config::SrtKind::Sls => {
+            let publisher = &data["publishers"][publisher];
 
-    let stream: Stat = serde_json::from_value(publisher.to_owned())?;
+            if publisher.is_null() {
+                return Ok(None);
+            }
 
-    Ok(stream.bitrate)
+            Ok(Some(serde_json::from_value(publisher.to_owned())?))
+        }
+        config::SrtKind::Mediamtx => {
+            let list: MediamtxList = serde_json::from_value(data)?;
+
+            let conn = list
+                .items
+                .into_iter()
+                .find(|c| c.state == "publish" && c.path == publisher.trim_start_matches('/'));
+
+            let conn = match conn {
+                Some(conn) => conn,
+                None => return Ok(None),
+            };
+
+            Ok(Some(Stat {
+                bitrate: (conn.mbps_receive_rate * 1000.0) as i64,
+                bytes_rcv_drop: conn.bytes_received_drop,
+                bytes_rcv_loss: conn.bytes_received_loss,
+                mbps_bandwidth: conn.mbps_link_capacity,
+                mbps_recv_rate: conn.mbps_receive_rate,
+                ms_rcv_buf: conn.ms_receive_buf,
+                pkt_rcv_drop: conn.packets_received_drop as i32,
+                pkt_rcv_loss: conn.packets_received_loss as i32,
+                rtt: conn.ms_rtt,
+                // MediaMTX doesn't tell how long the connection is up
+                uptime: 0,
+            }))
+        }
+    }
 }
 
-pub async fn srt_bitrate_monitor(config: &config::Srt, srt_bitrate_sync: Arc<Mutex<i64>>){
+/// Keeps the status and the latest stats of the publisher
+pub async fn srt_stats_monitor(config: &config::Srt, srt_sync: Arc<Mutex<SrtStatus>>){
+    let interval = tokio::time::Duration::from_millis(config.poll_interval.max(100));
+
     loop{
-        tokio::time::sleep(tokio::time::Duration::from_secs(1)).await;
-
-        if let Ok(bitrate) = get_srt_bitrate(config).await{
-            let mut srt_bitrate = srt_bitrate_sync.lock().await;
-            *srt_bitrate = bitrate;
-        }else{
-            let mut srt_bitrate = srt_bitrate_sync.lock().await;
-            *srt_bitrate = 0; 
+        tokio::time::sleep(interval).await;
+
+        let status = SrtStatus::from(get_srt_stats(config).await);
+
+        let mut srt_status = srt_sync.lock().await;
+
+        // the server is up, so it's most likely the config
+        if let SrtStatus::InvalidStats { reason } = &status {
+            if !matches!(*srt_status, SrtStatus::InvalidStats { .. }) {
+                println!("Srt: can't read the stats of {}, is the kind right? {}", config.url, reason);
+            }
         }
+
+        *srt_status = status;
+    }
+}
+
+/// Warns when the publisher loses or drops too many packets or the RTT is too high,
+/// and tells when the link is stable again
+pub async fn srt_alerts_monitor(
+    alerts: config::SrtAlerts,
+    srt_sync: Arc<Mutex<SrtStatus>>,
+    events: broadcast::Sender<Event>,
+) {
+    let interval = alerts.interval.max(1);
+    let mut previous: Option<Stat> = None;
+    let mut unstable = false;
+    let mut stable_intervals = 0;
+
+    loop {
+        tokio::time::sleep(tokio::time::Duration::from_secs(interval)).await;
+
+        // an offline publisher isn't losing packets, the stream monitor tells about that
+        let current = match srt_sync.lock().await.stat().cloned() {
+            Some(current) => current,
+            None => {
+                previous = None;
+                continue;
+            }
+        };
+
+        let last = match previous.replace(current.clone()) {
+            Some(last) => last,
+            None => continue,
+        };
+
+        let loss_rate = counted_since(last.pkt_rcv_loss, current.pkt_rcv_loss) as f64 / interval as f64;
+        let drop_rate = counted_since(last.pkt_rcv_drop, current.pkt_rcv_drop) as f64 / interval as f64;
+
+        let exceeds = |threshold: Option<f64>, value: f64| threshold.map(|t| value > t).unwrap_or(false);
+
+        let bad = exceeds(alerts.loss_rate, loss_rate)
+            || exceeds(alerts.drop_rate, drop_rate)
+            || exceeds(alerts.rtt, current.rtt);
+
+        if bad {
+            stable_intervals = 0;
+
+            if !unstable {
+                unstable = true;
+                let _ = events.send(Event::SrtUnstable {
+                    loss_rate,
+                    drop_rate,
+                    rtt: current.rtt,
+                });
+            }
+        } else if unstable {
+            stable_intervals += 1;
+
+            if stable_intervals >= alerts.recovery_intervals {
+                unstable = false;
+                stable_intervals = 0;
+                let _ = events.send(Event::SrtStable);
+            }
+        }
+    }
+}
+
+/// Packets counted since the last time, the counters start over when the publisher reconnects
+fn counted_since(last: i32, current: i32) -> i32 {
+    if current >= last {
+        current - last
+    } else {
+        current
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use config::SrtKind;
+
+    fn fixture(json: &str) -> Value {
+        serde_json::from_str(json).unwrap()
+    }
+
+    #[test]
+    fn parses_sls() {
+        let data = fixture(include_str!("../tests/fixtures/srt/sls.json"));
+        let stat = parse_stats(SrtKind::Sls, "publish/live/feed1", data).unwrap().unwrap();
+
+        assert_eq!(stat.bitrate, 6019);
+        assert_eq!(stat.pkt_rcv_loss, 11);
+        assert_eq!(stat.pkt_rcv_drop, 2);
+        assert_eq!(stat.ms_rcv_buf, 1996);
+        assert_eq!(stat.rtt, 38.243);
+        assert_eq!(stat.uptime, 4215);
+    }
+
+    #[test]
+    fn sls_publisher_missing() {
+        let data = fixture(include_str!("../tests/fixtures/srt/sls.json"));
+        assert!(parse_stats(SrtKind::Sls, "publish/live/other", data).unwrap().is_none());
+
+        let data = fixture(include_str!("../tests/fixtures/srt/sls_no_publisher.json"));
+        assert!(parse_stats(SrtKind::Sls, "publish/live/feed1", data).unwrap().is_none());
+    }
+
+    #[test]
+    fn parses_mediamtx() {
+        let data = fixture(include_str!("../tests/fixtures/srt/mediamtx.json"));
+        let stat = parse_stats(SrtKind::Mediamtx, "live", data).unwrap().unwrap();
+
+        assert_eq!(stat.bitrate, 6021);
+        assert_eq!(stat.pkt_rcv_loss, 11);
+        assert_eq!(stat.pkt_rcv_drop, 2);
+        assert_eq!(stat.bytes_rcv_loss, 14476);
+        assert_eq!(stat.ms_rcv_buf, 118);
+        assert_eq!(stat.rtt, 38.243);
+        assert_eq!(stat.mbps_bandwidth, 89.496);
+    }
+
+    #[test]
+    fn mediamtx_publisher_missing() {
+        let data = fixture(include_str!("../tests/fixtures/srt/mediamtx.json"));
+        assert!(parse_stats(SrtKind::Mediamtx, "other", data).unwrap().is_none());
+
+        let data = fixture(include_str!("../tests/fixtures/srt/mediamtx_empty.json"));
+        assert!(parse_stats(SrtKind::Mediamtx, "live", data).unwrap().is_none());
+    }
+
+    #[test]
+    fn mediamtx_ignores_readers() {
+        let mut data = fixture(include_str!("../tests/fixtures/srt/mediamtx.json"));
+        data["items"].as_array_mut().unwrap().retain(|c| c["state"] != "publish");
+
+        assert!(parse_stats(SrtKind::Mediamtx, "live", data).unwrap().is_none());
+    }
+
+    #[test]
+    fn status_tells_unreachable_and_invalid_stats_apart() {
+        let down = SrtStatus::from(Err(Error::SrtDown("Can't connect to SRT stats".to_owned())));
+        assert_eq!(down.name(), "unreachable");
+
+        let html = serde_json::from_str::<Value>("<html></html>").map_err(Error::from);
+        let invalid = SrtStatus::from(html.map(|_| None));
+        assert_eq!(invalid.name(), "invalid_stats");
+
+        // the stats of sls read as mediamtx
+        let data = fixture(include_str!("../tests/fixtures/srt/sls.json"));
+        let wrong_kind = SrtStatus::from(parse_stats(SrtKind::Mediamtx, "live", data));
+        assert_eq!(wrong_kind.name(), "invalid_stats");
+
+        assert_eq!(SrtStatus::from(Ok(None)).name(), "not_publishing");
+        assert_eq!(SrtStatus::default().name(), "pending");
+    }
+
+    #[test]
+    fn belabox_is_sls() {
+        let srt: config::Srt =
+            serde_json::from_str(r#"{"url": "", "publisher": "", "kind": "belabox"}"#).unwrap();
+
+        assert_eq!(srt.kind, SrtKind::Sls);
     }
 }
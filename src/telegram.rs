@@ -0,0 +1,254 @@
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::sync::broadcast;
+
+use crate::{
+    commands::{Args, Command, CommandHandler, Reply},
+    config,
+    error::Error,
+    events::Event,
+};
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+
+/// Seconds telegram keeps a getUpdates request open while waiting for messages
+const POLL_TIMEOUT: u64 = 30;
+
+#[derive(Deserialize, Debug)]
+struct Response<T> {
+    ok: bool,
+    result: Option<T>,
+    description: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Update {
+    update_id: i64,
+    message: Option<Message>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Message {
+    chat: Chat,
+    from: Option<User>,
+    text: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct Chat {
+    id: i64,
+}
+
+#[derive(Deserialize, Debug)]
+struct User {
+    first_name: String,
+    username: Option<String>,
+}
+
+#[derive(Serialize, Debug)]
+struct GetUpdates {
+    offset: i64,
+    timeout: u64,
+    allowed_updates: Vec<&'static str>,
+}
+
+#[derive(Serialize, Debug)]
+struct SendMessage<'a> {
+    chat_id: i64,
+    text: &'a str,
+}
+
+#[derive(Clone)]
+pub struct Telegram {
+    config: config::Telegram,
+    handler: CommandHandler,
+    client: reqwest::Client,
+}
+
+/// Replies to a Telegram chat
+struct TelegramReply {
+    telegram: Telegram,
+    chat_id: i64,
+}
+
+#[async_trait]
+impl Reply for TelegramReply {
+    async fn reply(&self, message: String) {
+        let _ = self.telegram.send_message(self.chat_id, &message).await;
+    }
+}
+
+impl Telegram {
+    pub fn run(config: config::Telegram, handler: CommandHandler) -> tokio::task::JoinHandle<()> {
+        let telegram = Self {
+            config,
+            handler,
+            client: reqwest::Client::new(),
+        };
+
+        let announce = telegram.clone();
+        let events = telegram.handler.events.subscribe();
+        tokio::spawn(async move { announce.announce_events(events).await });
+
+        tokio::spawn(async move { telegram.poll().await })
+    }
+
+    /// Sends the monitor events allowed by the config to every chat
+    async fn announce_events(&self, mut events: broadcast::Receiver<Event>) {
+        loop {
+            let event = match events.recv().await {
+                Ok(event) => event,
+                Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                Err(broadcast::error::RecvError::Closed) => break,
+            };
+
+            if !self.config.events.allows(event.category()) {
+                continue;
+            }
+
+            let message = event.message(&self.handler.lang);
+
+            for chat_id in &self.config.chat_ids {
+                if let Err(e) = self.send_message(*chat_id, &message).await {
+                    println!("Telegram: failed to send notification: {}", e);
+                }
+            }
+        }
+    }
+
+    async fn poll(&self) {
+        let mut offset = 0;
+
+        loop {
+            let updates = match self.get_updates(offset).await {
+                Ok(updates) => updates,
+                Err(e) => {
+                    println!("Telegram: failed to get updates: {}", e);
+                    tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
+                    continue;
+                }
+            };
+
+            for update in updates {
+                offset = update.update_id + 1;
+
+                if let Some(message) = update.message {
+                    self.handle_message(message);
+                }
+            }
+        }
+    }
+
+    fn handle_message(&self, message: Message) {
+        // only the operators are allowed to talk to the bot
+        if !self.config.chat_ids.contains(&message.chat.id) {
+            return;
+        }
+
+        let text = match message.text {
+            Some(text) => text,
+            None => return,
+        };
+
+        let command = match self.get_command(&text) {
+            Command::Unknown => return,
+            command => command,
+        };
+
+        let user = match message.from {
+            Some(User {
+                username: Some(username),
+                ..
+            }) => username,
+            Some(User { first_name, .. }) => first_name,
+            None => "unknown".to_string(),
+        };
+
+        let args = Args::parse(text.split_ascii_whitespace().skip(1));
+        let telegram = self.clone();
+        let chat_id = message.chat.id;
+
+        // some commands take minutes, so don't hold up the other messages
+        tokio::spawn(async move {
+            let res = if !command.controls_unit() {
+                telegram.handler.handle_non_permission_commands(command, &args).await
+            } else {
+                telegram
+                    .handler
+                    .audit(text.trim(), &user, &format!("telegram {}", chat_id));
+
+                let reply = Arc::new(TelegramReply {
+                    telegram: telegram.clone(),
+                    chat_id,
+                });
+
+                telegram.handler.handle_permission_commands(command, &args, reply).await
+            };
+
+            if let Ok(res) = res {
+                if !res.is_empty() {
+                    let _ = telegram.send_message(chat_id, &res).await;
+                }
+            }
+        });
+    }
+
+    /// Accepts the commands from the config, and the telegram style `/lus` or `/lus@bot` as well
+    fn get_command(&self, text: &str) -> Command {
+        let word = text.split_ascii_whitespace().next().unwrap_or("");
+        let word = word.split('@').next().unwrap_or("");
+
+        match self.handler.get_command(word) {
+            Command::Unknown => match word.strip_prefix('/') {
+                Some(name) => self.handler.get_command(&format!("!{}", name)),
+                None => Command::Unknown,
+            },
+            command => command,
+        }
+    }
+
+    async fn get_updates(&self, offset: i64) -> Result<Vec<Update>, Error> {
+        self.request(
+            "getUpdates",
+            &GetUpdates {
+                offset,
+                timeout: POLL_TIMEOUT,
+                allowed_updates: vec!["message"],
+            },
+        )
+        .await
+    }
+
+    async fn send_message(&self, chat_id: i64, text: &str) -> Result<(), Error> {
+        let _: serde_json::Value = self
+            .request("sendMessage", &SendMessage { chat_id, text })
+            .await?;
+
+        Ok(())
+    }
+
+    async fn request<T: Serialize, R: DeserializeOwned>(
+        &self,
+        method: &str,
+        payload: &T,
+    ) -> Result<R, Error> {
+        let api = self.config.api_url.as_deref().unwrap_or(TELEGRAM_API);
+
+        let res: Response<R> = self
+            .client
+            .post(format!("{}/bot{}/{}", api, self.config.bot_token, method))
+            .timeout(std::time::Duration::from_secs(POLL_TIMEOUT + 10))
+            .json(payload)
+            .send()
+            .await?
+            .json()
+            .await?;
+
+        match res.result {
+            Some(result) if res.ok => Ok(result),
+            _ => Err(Error::Telegram(res.description.unwrap_or_default())),
+        }
+    }
+}
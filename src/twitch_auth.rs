@@ -0,0 +1,182 @@
+use std::{
+    fs,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Mutex;
+use twitch_irc::login::{CredentialsPair, LoginCredentials};
+
+use crate::{config, error::Error};
+
+const TWITCH_TOKEN_URL: &str = "https://id.twitch.tv/oauth2/token";
+const DEFAULT_TOKEN_FILE: &str = "twitch_token.json";
+
+/// Refresh this many seconds before the token actually expires
+const REFRESH_MARGIN: u64 = 300;
+
+/// The tokens saved to disk, refresh tokens can only be used once
+#[derive(Serialize, Deserialize, Debug, Clone)]
+#[serde(rename_all = "camelCase")]
+struct Token {
+    access_token: String,
+    refresh_token: String,
+    /// Unix timestamp, 0 when we don't know
+    expires_at: u64,
+    /// The refresh token of the config when this was saved, so a new one in the config is noticed
+    #[serde(default)]
+    config_refresh_token: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+struct TokenResponse {
+    access_token: String,
+    refresh_token: String,
+    expires_in: u64,
+}
+
+/// Logs in with the oauth from the config, or with a token that gets refreshed
+/// when `auth` is configured
+#[derive(Debug, Clone)]
+pub struct TwitchCredentials {
+    login: String,
+    oauth: String,
+    auth: Option<config::TwitchAuth>,
+    token: Arc<Mutex<Option<Token>>>,
+    /// Set when the token endpoint refused the refresh token
+    rejected: Arc<AtomicBool>,
+    client: reqwest::Client,
+}
+
+impl TwitchCredentials {
+    pub fn new(login: String, oauth: String, auth: Option<config::TwitchAuth>) -> Self {
+        let token = auth.as_ref().map(|auth| {
+            // the saved refresh token replaces the one in the config after the first refresh,
+            // unless the config got a different one since then
+            Self::load(auth)
+                .filter(|token| match &token.config_refresh_token {
+                    Some(config_token) => config_token == &auth.refresh_token,
+                    None => true,
+                })
+                .unwrap_or_else(|| Token {
+                    access_token: String::new(),
+                    refresh_token: auth.refresh_token.to_owned(),
+                    expires_at: 0,
+                    config_refresh_token: Some(auth.refresh_token.to_owned()),
+                })
+        });
+
+        Self {
+            login,
+            oauth,
+            auth,
+            token: Arc::new(Mutex::new(token)),
+            rejected: Arc::new(AtomicBool::new(false)),
+            client: reqwest::Client::new(),
+        }
+    }
+
+    /// Makes the next login refresh the token, e.g. after Twitch rejected it
+    pub async fn invalidate(&self) {
+        if let Some(token) = &mut *self.token.lock().await {
+            token.expires_at = 0;
+            token.access_token.clear();
+        }
+    }
+
+    pub fn is_refreshing(&self) -> bool {
+        self.auth.is_some()
+    }
+
+    /// The refresh token is invalid or revoked, logging in won't work anymore
+    pub fn is_rejected(&self) -> bool {
+        self.rejected.load(Ordering::Acquire)
+    }
+
+    async fn refresh(&self, auth: &config::TwitchAuth, token: &Token) -> Result<Token, Error> {
+        let res = self
+            .client
+            .post(auth.token_url.as_deref().unwrap_or(TWITCH_TOKEN_URL))
+            .form(&[
+                ("grant_type", "refresh_token"),
+                ("refresh_token", token.refresh_token.as_str()),
+                ("client_id", auth.client_id.as_str()),
+                ("client_secret", auth.client_secret.as_str()),
+            ])
+            .send()
+            .await?;
+
+        if res.status() == StatusCode::BAD_REQUEST || res.status() == StatusCode::UNAUTHORIZED {
+            self.rejected.store(true, Ordering::Release);
+        }
+
+        let res: TokenResponse = res.error_for_status()?.json().await?;
+
+        let token = Token {
+            access_token: res.access_token,
+            refresh_token: res.refresh_token,
+            expires_at: now() + res.expires_in,
+            config_refresh_token: Some(auth.refresh_token.to_owned()),
+        };
+
+        fs::write(token_file(auth), serde_json::to_string_pretty(&token)?)?;
+
+        Ok(token)
+    }
+
+    fn load(auth: &config::TwitchAuth) -> Option<Token> {
+        let file = fs::read_to_string(token_file(auth)).ok()?;
+        serde_json::from_str(&file).ok()
+    }
+}
+
+#[async_trait]
+impl LoginCredentials for TwitchCredentials {
+    type Error = Error;
+
+    async fn get_credentials(&self) -> Result<CredentialsPair, Error> {
+        let auth = match &self.auth {
+            Some(auth) => auth,
+            None => {
+                return Ok(CredentialsPair {
+                    login: self.login.to_owned(),
+                    token: Some(self.oauth.to_owned()),
+                })
+            }
+        };
+
+        let mut token = self.token.lock().await;
+        let current = token.clone().expect("token is set when refreshing");
+
+        let access_token = if current.expires_at <= now() + REFRESH_MARGIN {
+            println!("Twitch: Refreshing access token");
+            let new = self.refresh(auth, &current).await?;
+            *token = Some(new.to_owned());
+            new.access_token
+        } else {
+            current.access_token
+        };
+
+        Ok(CredentialsPair {
+            login: self.login.to_owned(),
+            token: Some(access_token),
+        })
+    }
+}
+
+fn token_file(auth: &config::TwitchAuth) -> &str {
+    auth.token_file.as_deref().unwrap_or(DEFAULT_TOKEN_FILE)
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,128 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hmac::{Hmac, Mac};
+use reqwest::{header::CONTENT_TYPE, StatusCode};
+use serde::Serialize;
+use sha2::Sha256;
+use tokio::sync::broadcast;
+
+use crate::{
+    config,
+    error::Error,
+    events::{Category, Event},
+};
+
+/// Header containing the hex encoded HMAC-SHA256 of the body, prefixed with `sha256=`
+pub const SIGNATURE_HEADER: &str = "X-LiveU-Signature-256";
+
+/// Longest wait in seconds between two retries
+const MAX_RETRY_DELAY: u64 = 300;
+
+/// The JSON body that is sent to the webhooks
+#[derive(Serialize, Debug)]
+pub struct Payload<'a> {
+    pub unit: &'a str,
+    pub timestamp: u64,
+    pub category: Category,
+    pub message: String,
+    #[serde(flatten)]
+    pub event: &'a Event,
+}
+
+/// Posts every event allowed by the config to the webhook
+pub async fn run(
+    config: config::Webhook,
+    boss_id: String,
+    lang: String,
+    mut events: broadcast::Receiver<Event>,
+) {
+    loop {
+        let event = match events.recv().await {
+            Ok(event) => event,
+            Err(broadcast::error::RecvError::Lagged(_)) => continue,
+            Err(broadcast::error::RecvError::Closed) => break,
+        };
+
+        if !config.events.allows(event.category()) {
+            continue;
+        }
+
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|d| d.as_secs())
+            .unwrap_or_default();
+
+        let payload = Payload {
+            unit: &boss_id,
+            timestamp,
+            category: event.category(),
+            message: event.message(&lang),
+            event: &event,
+        };
+
+        let body = match serde_json::to_string(&payload) {
+            Ok(body) => body,
+            Err(e) => {
+                println!("Webhook: failed to serialize event: {}", e);
+                continue;
+            }
+        };
+
+        if let Err(e) = send_with_retries(&config, body).await {
+            println!("Webhook: failed to send to {}: {}", config.url, e);
+        }
+    }
+}
+
+async fn send_with_retries(config: &config::Webhook, body: String) -> Result<(), Error> {
+    let mut delay = config.retry_delay;
+    let mut attempt = 0;
+
+    loop {
+        let res = send(config, body.clone()).await;
+
+        if res.is_ok() || attempt == config.retries || !is_retryable(&res) {
+            return res;
+        }
+
+        tokio::time::sleep(tokio::time::Duration::from_secs(delay)).await;
+        delay = delay.saturating_mul(2).min(MAX_RETRY_DELAY);
+        attempt += 1;
+    }
+}
+
+/// Sending the same request again won't fix client errors, except for rate limits
+fn is_retryable(res: &Result<(), Error>) -> bool {
+    let status = match res {
+        Err(Error::RequestFailed(e)) => e.status(),
+        _ => None,
+    };
+
+    match status {
+        Some(status) => !status.is_client_error() || status == StatusCode::TOO_MANY_REQUESTS,
+        None => true,
+    }
+}
+
+async fn send(config: &config::Webhook, body: String) -> Result<(), Error> {
+    let mut req = reqwest::Client::new()
+        .post(&config.url)
+        .header(CONTENT_TYPE, "application/json");
+
+    if let Some(secret) = &config.secret {
+        req = req.header(SIGNATURE_HEADER, format!("sha256={}", sign(secret, &body)));
+    }
+
+    req.body(body).send().await?.error_for_status()?;
+
+    Ok(())
+}
+
+/// Hex encoded HMAC-SHA256 of the body
+pub fn sign(secret: &str, body: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())
+        .expect("HMAC can take a key of any size");
+    mac.update(body.as_bytes());
+
+    hex::encode(mac.finalize().into_bytes())
+}
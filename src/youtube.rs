@@ -0,0 +1,343 @@
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use reqwest::StatusCode;
+use serde::Deserialize;
+use serde_json::json;
+use tokio::sync::{mpsc, Mutex};
+
+use crate::{
+    chat::{ChatMessage, ChatPlatform, Role},
+    config,
+    error::Error,
+};
+
+const YOUTUBE_API: &str = "https://www.googleapis.com/youtube/v3";
+const GOOGLE_TOKEN_URL: &str = "https://oauth2.googleapis.com/token";
+
+/// Every request to the chat costs quota, so don't poll faster than this by default
+const DEFAULT_POLL_INTERVAL: u64 = 20;
+
+#[derive(Deserialize, Debug)]
+struct Token {
+    access_token: String,
+    expires_in: u64,
+}
+
+#[derive(Deserialize, Debug)]
+struct List<T> {
+    items: Vec<T>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Broadcast {
+    snippet: BroadcastSnippet,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct BroadcastSnippet {
+    live_chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Video {
+    live_streaming_details: Option<LiveStreamingDetails>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct LiveStreamingDetails {
+    active_live_chat_id: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Messages {
+    next_page_token: Option<String>,
+    polling_interval_millis: Option<u64>,
+    items: Vec<Message>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Message {
+    snippet: MessageSnippet,
+    author_details: AuthorDetails,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct MessageSnippet {
+    display_message: Option<String>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct AuthorDetails {
+    channel_id: String,
+    display_name: String,
+    is_chat_owner: bool,
+    is_chat_moderator: bool,
+    is_chat_sponsor: bool,
+}
+
+pub struct Youtube {
+    config: config::Youtube,
+    client: reqwest::Client,
+    access_token: Mutex<Option<(String, Instant)>>,
+    live_chat_id: Mutex<Option<String>>,
+}
+
+impl Youtube {
+    pub fn new(config: config::Youtube) -> Self {
+        Self {
+            config,
+            client: reqwest::Client::new(),
+            access_token: Mutex::new(None),
+            live_chat_id: Mutex::new(None),
+        }
+    }
+
+    /// Gets a new access token with the refresh token when the old one expired
+    async fn get_access_token(&self) -> Result<String, Error> {
+        let mut access_token = self.access_token.lock().await;
+
+        if let Some((token, expires)) = &*access_token {
+            if Instant::now() < *expires {
+                return Ok(token.to_owned());
+            }
+        }
+
+        let token: Token = self
+            .client
+            .post(GOOGLE_TOKEN_URL)
+            .form(&[
+                ("client_id", self.config.client_id.as_str()),
+                ("client_secret", self.config.client_secret.as_str()),
+                ("refresh_token", self.config.refresh_token.as_str()),
+                ("grant_type", "refresh_token"),
+            ])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        // refresh a minute early so a request never uses an expired token
+        let expires = Instant::now() + Duration::from_secs(token.expires_in.saturating_sub(60));
+        *access_token = Some((token.access_token.to_owned(), expires));
+
+        Ok(token.access_token)
+    }
+
+    async fn find_live_chat_id(&self) -> Result<Option<String>, Error> {
+        let token = self.get_access_token().await?;
+
+        if let Some(video_id) = &self.config.video_id {
+            let videos: List<Video> = self
+                .client
+                .get(format!("{}/videos", YOUTUBE_API))
+                .query(&[("part", "liveStreamingDetails"), ("id", video_id)])
+                .bearer_auth(token)
+                .send()
+                .await?
+                .error_for_status()?
+                .json()
+                .await?;
+
+            return Ok(videos
+                .items
+                .into_iter()
+                .find_map(|v| v.live_streaming_details?.active_live_chat_id));
+        }
+
+        let broadcasts: List<Broadcast> = self
+            .client
+            .get(format!("{}/liveBroadcasts", YOUTUBE_API))
+            .query(&[
+                ("part", "snippet"),
+                ("broadcastStatus", "active"),
+                ("broadcastType", "all"),
+            ])
+            .bearer_auth(token)
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        Ok(broadcasts
+            .items
+            .into_iter()
+            .find_map(|b| b.snippet.live_chat_id))
+    }
+
+    async fn get_messages(
+        &self,
+        live_chat_id: &str,
+        page_token: Option<&str>,
+    ) -> Result<Option<Messages>, Error> {
+        let token = self.get_access_token().await?;
+
+        let mut query = vec![
+            ("liveChatId", live_chat_id),
+            ("part", "snippet,authorDetails"),
+        ];
+
+        if let Some(page_token) = page_token {
+            query.push(("pageToken", page_token));
+        }
+
+        let res = self
+            .client
+            .get(format!("{}/liveChat/messages", YOUTUBE_API))
+            .query(&query)
+            .bearer_auth(token)
+            .send()
+            .await?;
+
+        // the chat is gone once the stream has ended
+        if res.status() == StatusCode::FORBIDDEN || res.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+
+        Ok(Some(res.error_for_status()?.json().await?))
+    }
+
+    fn get_role(&self, author: &AuthorDetails) -> Role {
+        // display names aren't unique, anyone could take the name of an admin
+        let is_admin = self
+            .config
+            .admin_users
+            .iter()
+            .flatten()
+            .any(|channel_id| channel_id == &author.channel_id);
+
+        if author.is_chat_owner {
+            Role::Broadcaster
+        } else if is_admin {
+            Role::Admin
+        } else if author.is_chat_moderator {
+            Role::Moderator
+        } else if author.is_chat_sponsor {
+            Role::Subscriber
+        } else {
+            Role::Everyone
+        }
+    }
+}
+
+#[async_trait]
+impl ChatPlatform for Youtube {
+    fn name(&self) -> &'static str {
+        "youtube"
+    }
+
+    fn mod_only(&self) -> bool {
+        self.config.mod_only
+    }
+
+    async fn channels(&self) -> Vec<String> {
+        self.live_chat_id.lock().await.iter().cloned().collect()
+    }
+
+    async fn send(&self, channel: &str, message: String) {
+        let token = match self.get_access_token().await {
+            Ok(token) => token,
+            Err(e) => {
+                println!("YouTube: failed to get access token: {}", e);
+                return;
+            }
+        };
+
+        let body = json!({
+            "snippet": {
+                "liveChatId": channel,
+                "type": "textMessageEvent",
+                "textMessageDetails": { "messageText": message },
+            }
+        });
+
+        let res = self
+            .client
+            .post(format!("{}/liveChat/messages", YOUTUBE_API))
+            .query(&[("part", "snippet")])
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+            .await;
+
+        if let Err(e) = res.and_then(|r| r.error_for_status()) {
+            println!("YouTube: failed to send message: {}", e);
+        }
+    }
+
+    async fn receive(&self, messages: mpsc::UnboundedSender<ChatMessage>) -> Result<(), Error> {
+        let poll_interval = self.config.poll_interval.unwrap_or(DEFAULT_POLL_INTERVAL);
+        let mut page_token: Option<String> = None;
+
+        loop {
+            let live_chat_id = self.live_chat_id.lock().await.clone();
+
+            let live_chat_id = match live_chat_id {
+                Some(id) => id,
+                None => {
+                    match self.find_live_chat_id().await {
+                        Ok(Some(id)) => {
+                            println!("YouTube: Connected to live chat");
+                            *self.live_chat_id.lock().await = Some(id);
+                        }
+                        Ok(None) => {}
+                        Err(e) => println!("YouTube: failed to find live chat: {}", e),
+                    }
+
+                    page_token = None;
+                    tokio::time::sleep(Duration::from_secs(30)).await;
+                    continue;
+                }
+            };
+
+            let res = match self.get_messages(&live_chat_id, page_token.as_deref()).await {
+                Ok(Some(res)) => res,
+                Ok(None) => {
+                    println!("YouTube: live chat ended");
+                    *self.live_chat_id.lock().await = None;
+                    continue;
+                }
+                Err(e) => {
+                    println!("YouTube: failed to get chat messages: {}", e);
+                    tokio::time::sleep(Duration::from_secs(poll_interval)).await;
+                    continue;
+                }
+            };
+
+            // the first page is the chat history, don't run old commands
+            if page_token.is_some() {
+                for message in res.items {
+                    let text = match message.snippet.display_message {
+                        Some(text) => text,
+                        None => continue,
+                    };
+
+                    let _ = messages.send(ChatMessage {
+                        channel: live_chat_id.to_owned(),
+                        role: self.get_role(&message.author_details),
+                        user: message.author_details.display_name,
+                        text,
+                    });
+                }
+            }
+
+            page_token = res.next_page_token;
+
+            let wait = res
+                .polling_interval_millis
+                .unwrap_or_default()
+                .max(poll_interval * 1000);
+            tokio::time::sleep(Duration::from_millis(wait)).await;
+        }
+    }
+}